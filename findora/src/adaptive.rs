@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+/// AIMD concurrency controller
+///
+/// Concurrency grows by `step` after every round whose latency stays below the target,
+/// and is halved as soon as it climbs above it.
+#[derive(Debug)]
+pub struct Aimd {
+    target: Duration,
+    step: usize,
+    max: usize,
+    level: usize,
+    trajectory: Vec<usize>,
+}
+
+impl Aimd {
+    pub fn new(target: Duration, step: usize, max: usize) -> Self {
+        let step = step.max(1);
+        let max = max.max(1);
+        Self {
+            target,
+            step,
+            max,
+            level: step.min(max),
+            trajectory: vec![],
+        }
+    }
+
+    /// concurrency to use for the next round
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// feed the latency observed at the current level, returns the next level
+    pub fn update(&mut self, latency: Duration) -> usize {
+        self.trajectory.push(self.level);
+        self.level = if latency <= self.target {
+            (self.level + self.step).min(self.max)
+        } else {
+            (self.level / 2).max(1)
+        };
+        self.level
+    }
+
+    /// levels used by every finished round
+    pub fn trajectory(&self) -> &[usize] {
+        &self.trajectory
+    }
+
+    /// average level of the last half of the rounds, where the controller has settled
+    pub fn steady_state(&self) -> usize {
+        let tail = &self.trajectory[self.trajectory.len() / 2..];
+        if tail.is_empty() {
+            self.level
+        } else {
+            (tail.iter().sum::<usize>() as f64 / tail.len() as f64).round() as usize
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::metrics::Snapshot;
+use std::fmt::{Display, Formatter};
+
+/// metrics which assertions can refer to
+const METRICS: [&str; 10] = [
+    "tps",
+    "sent",
+    "succeed",
+    "reverted",
+    "failed",
+    "error_rate",
+    "revert_rate",
+    "p50_latency_ms",
+    "p95_latency_ms",
+    "seconds",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Ge,
+    Gt,
+    Le,
+    Lt,
+    Eq,
+}
+
+impl Op {
+    fn eval(&self, actual: f64, expected: f64) -> bool {
+        match self {
+            Self::Ge => actual >= expected,
+            Self::Gt => actual > expected,
+            Self::Le => actual <= expected,
+            Self::Lt => actual < expected,
+            Self::Eq => (actual - expected).abs() < f64::EPSILON,
+        }
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            Self::Ge => ">=",
+            Self::Gt => ">",
+            Self::Le => "<=",
+            Self::Lt => "<",
+            Self::Eq => "==",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// Post-run check like `tps>=500`, used to gate CI on performance regressions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assertion {
+    metric: String,
+    op: Op,
+    value: f64,
+}
+
+impl std::str::FromStr for Assertion {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // two-character operators first, so `>=` is not taken as `>`
+        let (metric, op, value) = [
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            ("==", Op::Eq),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+        ]
+        .iter()
+        .find_map(|(sym, op)| s.split_once(sym).map(|(m, v)| (m.trim(), *op, v.trim())))
+        .ok_or_else(|| {
+            format!(
+                "Invalid assertion {}, expect <metric><op><value>, op is one of >=, >, <=, <, ==",
+                s
+            )
+        })?;
+        if !METRICS.contains(&metric) {
+            return Err(format!(
+                "Invalid assertion metric {}, supported: {}",
+                metric,
+                METRICS.join(", ")
+            ));
+        }
+        let value = value
+            .parse::<f64>()
+            .map_err(|_| format!("Invalid assertion value {} of {}", value, s))?;
+        Ok(Self {
+            metric: metric.to_owned(),
+            op,
+            value,
+        })
+    }
+}
+
+impl Display for Assertion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}{}", self.metric, self.op, self.value)
+    }
+}
+
+impl Assertion {
+    /// the actual value if the assertion fails
+    pub fn check(&self, snapshot: &Snapshot) -> Option<f64> {
+        let actual = match self.metric.as_str() {
+            "tps" => snapshot.tps,
+            "sent" => snapshot.sent as f64,
+            "succeed" => snapshot.succeed as f64,
+            "reverted" => snapshot.reverted as f64,
+            "failed" => snapshot.failed as f64,
+            "error_rate" => snapshot.error_rate,
+            "revert_rate" if snapshot.sent == 0 => 0f64,
+            "revert_rate" => snapshot.reverted as f64 / snapshot.sent as f64,
+            "p50_latency_ms" => snapshot.latency_p50,
+            "p95_latency_ms" => snapshot.latency_p95,
+            "seconds" => snapshot.elapsed as f64,
+            _ => unreachable!("metric {} is checked when parsing", self.metric),
+        };
+        (!self.op.eval(actual, self.value)).then(|| actual)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn assertion(metric: &str, op: Op, value: f64) -> Assertion {
+        Assertion {
+            metric: metric.to_owned(),
+            op,
+            value,
+        }
+    }
+
+    #[test]
+    fn assertion_parse() {
+        assert_eq!(Assertion::from_str("tps>=500"), Ok(assertion("tps", Op::Ge, 500f64)));
+        assert_eq!(
+            Assertion::from_str("p95_latency_ms<2000"),
+            Ok(assertion("p95_latency_ms", Op::Lt, 2000f64))
+        );
+        assert_eq!(
+            Assertion::from_str(" error_rate <= 0.01 "),
+            Ok(assertion("error_rate", Op::Le, 0.01))
+        );
+        assert_eq!(Assertion::from_str("sent==10"), Ok(assertion("sent", Op::Eq, 10f64)));
+        assert_eq!(Assertion::from_str("tps>500"), Ok(assertion("tps", Op::Gt, 500f64)));
+        assert_eq!(Assertion::from_str("tps>=500").unwrap().to_string(), "tps>=500");
+    }
+
+    #[test]
+    fn assertion_invalid() {
+        // a single `=` is not an operator
+        assert!(Assertion::from_str("tps=5").is_err());
+        assert!(Assertion::from_str("foo>1").is_err());
+        assert!(Assertion::from_str("tps>=").is_err());
+        assert!(Assertion::from_str("tps>=fast").is_err());
+        assert!(Assertion::from_str("tps>==5").is_err());
+        assert!(Assertion::from_str("tps").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use crate::metrics::Snapshot;
+use serde_json::Value;
+use std::path::Path;
+
+/// One metric of a run compared to the same metric of a baseline run
+#[derive(Debug)]
+pub struct Delta {
+    pub name: &'static str,
+    pub before: f64,
+    pub after: f64,
+    /// relative change in percent, `None` if the baseline is zero
+    pub pct: Option<f64>,
+    /// the change goes the wrong way by more than the allowed percentage
+    pub regressed: bool,
+}
+
+/// load the metrics of a previous `--summary-json`
+pub fn load(path: &Path) -> anyhow::Result<Snapshot> {
+    let summary: Value = serde_json::from_str(std::fs::read_to_string(path)?.as_str())?;
+    if summary["completed"] == Value::Bool(false) {
+        log::warn!("baseline {:?} comes from an interrupted run", path);
+    }
+    serde_json::from_value(summary["metrics"].clone())
+        .map_err(|e| anyhow::anyhow!("invalid baseline {:?}, expect a --summary-json file: {}", path, e))
+}
+
+/// compare TPS and latencies, TPS regresses when it drops, latencies when they rise
+pub fn compare(baseline: &Snapshot, current: &Snapshot, regression_pct: f64) -> Vec<Delta> {
+    [
+        ("tps", baseline.tps, current.tps, false),
+        ("p50_latency_ms", baseline.latency_p50, current.latency_p50, true),
+        ("p95_latency_ms", baseline.latency_p95, current.latency_p95, true),
+    ]
+    .into_iter()
+    .map(|(name, before, after, lower_is_better)| {
+        let pct = (before != 0f64).then(|| (after - before) / before * 100f64);
+        let regressed = pct.map_or(false, |pct| {
+            if lower_is_better {
+                pct > regression_pct
+            } else {
+                pct < -regression_pct
+            }
+        });
+        Delta {
+            name,
+            before,
+            after,
+            pct,
+            regressed,
+        }
+    })
+    .collect()
+}
+
+/// print the before/after table
+pub fn print(deltas: &[Delta]) {
+    log::info!("{:<16}{:>12}{:>12}{:>10}", "metric", "baseline", "current", "delta");
+    for d in deltas {
+        let pct = d.pct.map_or_else(|| "-".to_owned(), |pct| format!("{:+.1}%", pct));
+        let line = format!("{:<16}{:>12.3}{:>12.3}{:>10}", d.name, d.before, d.after, pct);
+        if d.regressed {
+            log::error!("{} regression", line);
+        } else {
+            log::info!("{}", line);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Accounts confirmed funded by a Fund run, so an interrupted run resumes without funding twice
+
+use feth::TransferMetrics;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use web3::types::{Address, H256};
+
+/// accounts funded between two writes of the checkpoint
+pub const CHECKPOINT_INTERVAL: usize = 100;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    #[serde(skip)]
+    path: PathBuf,
+    /// funding transaction of every confirmed account
+    funded: BTreeMap<Address, H256>,
+}
+
+impl Checkpoint {
+    /// load the checkpoint at `path`, empty if it does not exist yet
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut checkpoint = match std::fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str::<Self>(&data)
+                .map_err(|e| anyhow::anyhow!("invalid fund checkpoint {:?}: {}", path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e.into()),
+        };
+        checkpoint.path = path.to_path_buf();
+        if !checkpoint.funded.is_empty() {
+            log::info!(
+                "resuming from {:?}, {} accounts already funded",
+                path,
+                checkpoint.funded.len()
+            );
+        }
+        Ok(checkpoint)
+    }
+
+    pub fn contains(&self, address: &Address) -> bool {
+        self.funded.contains_key(address)
+    }
+
+    /// number of accounts funded so far
+    pub fn funded(&self) -> usize {
+        self.funded.len()
+    }
+
+    /// add the confirmed transfers of a distribution and rewrite the file
+    pub fn record(&mut self, metrics: &TransferMetrics) -> anyhow::Result<()> {
+        self.funded.extend(
+            metrics
+                .txs
+                .iter()
+                .filter(|tx| tx.status == 1)
+                .filter_map(|tx| tx.hash.map(|hash| (tx.to, hash))),
+        );
+        self.save()
+    }
+
+    /// write a temporary file and rename it over the checkpoint, a crash leaves either version whole
+    fn save(&self) -> anyhow::Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+//! Colored log lines: warnings in yellow, errors in red and run summaries in green
+//!
+//! Colors are only written to a terminal, they are off with `--no-color`, a non-empty
+//! `NO_COLOR` env or when stderr is redirected, so they never end up in files or pipes.
+
+use crossterm::{
+    style::{Color, Stylize},
+    tty::IsTty,
+};
+use env_logger::fmt::Formatter;
+use log::{Level, Record};
+use std::{
+    fmt::Display,
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering::Relaxed},
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// decide once whether to color the log lines written to stderr
+pub fn init(no_color: bool) {
+    let no_color_env = std::env::var_os("NO_COLOR").map_or(false, |v| !v.is_empty());
+    ENABLED.store(!no_color && !no_color_env && std::io::stderr().is_tty(), Relaxed);
+}
+
+fn paint(text: impl Display, color: Color) -> String {
+    if ENABLED.load(Relaxed) {
+        text.to_string().with(color).to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// a summary of a successful run
+pub fn success(text: impl Display) -> String {
+    paint(text, Color::Green)
+}
+
+/// env_logger format, the default layout with warning and error lines colored
+pub fn format(buf: &mut Formatter, record: &Record) -> std::io::Result<()> {
+    let line = format!(
+        "[{} {:<5} {}] {}",
+        buf.timestamp(),
+        record.level(),
+        record.module_path().unwrap_or_default(),
+        record.args()
+    );
+    let line = match record.level() {
+        Level::Error => paint(line, Color::Red),
+        Level::Warn => paint(line, Color::Yellow),
+        _ => line,
+    };
+    writeln!(buf, "{}", line)
+}
@@ -1,23 +1,37 @@
 use crate::{
-    db::{Db, Proto},
-    profiler,
+    assertion::Assertion,
+    db::{Codec, Db, Proto, Store},
+    output::{self, Format, HashEncoding, Output, Rotate},
+    profiler, report,
+    stats::Summary,
+    template::DataTemplate,
 };
 use chrono::NaiveDateTime;
 use clap::{Parser, Subcommand};
-use feth::{error::Result, BLOCK_TIME};
+use feth::{
+    error::{Error, Result, ResultExt},
+    transport::Jitter,
+    TestClient, TxType, BLOCK_TIME, TRANSFER_GAS,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
     fmt::{Display, Formatter},
-    io::BufRead,
+    io::{BufRead, BufWriter, Write},
+    ops::RangeInclusive,
     path::{Path, PathBuf},
     rc::Rc,
+    time::{Duration, Instant},
 };
-use web3::types::{Address, H256};
+use web3::types::{Address, BlockId, BlockNumber, H256};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum TestMode {
     Basic,
     Contract,
+    /// zero-value transfers of every source account to itself, no recipients to fund
+    SelfTx,
 }
 
 impl std::str::FromStr for TestMode {
@@ -27,7 +41,172 @@ impl std::str::FromStr for TestMode {
         match s.to_lowercase().as_str() {
             "basic" => Ok(Self::Basic),
             "contract" => Ok(Self::Contract),
-            _ => Err("Invalid mode: basic and contract are supported".to_owned()),
+            "self" => Ok(Self::SelfTx),
+            _ => Err("Invalid mode: basic, contract and self are supported".to_owned()),
+        }
+    }
+}
+
+/// Recipients of the test transfers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecipientPattern {
+    /// every source account to itself
+    SelfTx,
+    /// to the next accounts of the key file, round-robin
+    Pool,
+    /// all to one address
+    Fixed(Address),
+    /// a new random address per transaction
+    Random,
+}
+
+impl std::str::FromStr for RecipientPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "self" => Ok(Self::SelfTx),
+            "pool" => Ok(Self::Pool),
+            "random" => Ok(Self::Random),
+            p => match p.strip_prefix("fixed:").map(str::parse::<Address>) {
+                Some(Ok(address)) => Ok(Self::Fixed(address)),
+                _ => Err("Invalid recipient pattern: self, pool, fixed:<address> and random are supported".to_owned()),
+            },
+        }
+    }
+}
+
+impl Display for RecipientPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SelfTx => write!(f, "self"),
+            Self::Pool => write!(f, "pool"),
+            Self::Fixed(address) => write!(f, "fixed:{:?}", address),
+            Self::Random => write!(f, "random"),
+        }
+    }
+}
+
+/// What to do when the tendermint log executes the same height more than once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDuplicate {
+    First,
+    Last,
+    Warn,
+}
+
+impl std::str::FromStr for OnDuplicate {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "first" => Ok(Self::First),
+            "last" => Ok(Self::Last),
+            "warn" => Ok(Self::Warn),
+            _ => Err("Invalid policy: first, last and warn are supported".to_owned()),
+        }
+    }
+}
+
+/// What to do with a test transaction still unconfirmed at its deadline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDeadline {
+    Fail,
+    /// resend it at the same nonce with a higher gas price
+    Bump,
+    /// replace it with a zero-value self-transfer at the same nonce
+    Cancel,
+}
+
+impl std::str::FromStr for OnDeadline {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fail" => Ok(Self::Fail),
+            "bump" => Ok(Self::Bump),
+            "cancel" => Ok(Self::Cancel),
+            _ => Err("Invalid deadline action: fail, bump and cancel are supported".to_owned()),
+        }
+    }
+}
+
+/// What to do when no test transaction completes for the stall window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnStall {
+    /// dump the diagnostics, stop sending and fail the run after writing its summary
+    Abort,
+    /// dump the diagnostics and keep waiting
+    Continue,
+}
+
+impl std::str::FromStr for OnStall {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "abort" => Ok(Self::Abort),
+            "continue" => Ok(Self::Continue),
+            _ => Err("Invalid stall action: abort and continue are supported".to_owned()),
+        }
+    }
+}
+
+/// A block height or one of the standard json-rpc block tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockTag {
+    Number(u64),
+    Latest,
+    Earliest,
+    Pending,
+    /// tendermint commits are final, so this is the latest block
+    Finalized,
+}
+
+impl std::str::FromStr for BlockTag {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "latest" => Ok(Self::Latest),
+            "earliest" => Ok(Self::Earliest),
+            "pending" => Ok(Self::Pending),
+            "finalized" => Ok(Self::Finalized),
+            height => match height.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => height.parse::<u64>(),
+            }
+            .map(Self::Number)
+            .map_err(|_| {
+                format!(
+                    "Invalid block {}, a height or one of latest, earliest, pending and finalized is supported",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+impl BlockTag {
+    /// resolve the tag to a concrete height on the node, for range arithmetic
+    pub fn resolve(&self, client: &TestClient) -> Result<u64> {
+        let latest = || {
+            client
+                .block_number()
+                .map(|n| n.as_u64())
+                .ok_or_else(|| Error::NodeUnreachable("cannot get the latest block height of the node".to_owned()))
+        };
+        match self {
+            BlockTag::Number(height) => Ok(*height),
+            BlockTag::Earliest => Ok(0),
+            BlockTag::Latest | BlockTag::Finalized => latest(),
+            BlockTag::Pending => match client
+                .block_with_tx_hashes(BlockId::Number(BlockNumber::Pending))
+                .and_then(|b| b.number)
+            {
+                Some(n) => Ok(n.as_u64()),
+                None => latest().map(|n| n + 1),
+            },
         }
     }
 }
@@ -131,11 +310,141 @@ impl std::str::FromStr for ContractOP {
     }
 }
 
+/// parse a count-like argument: digits with optional underscores between them, and an optional
+/// decimal suffix k (10^3), m (10^6) or g (10^9), e.g. `1_000_000`, `10k`, `1.5m`
+fn parse_count(s: &str) -> std::result::Result<u64, String> {
+    let invalid = |why: &str| format!("Invalid count {}, {}", s, why);
+    let (number, scale) = match s.char_indices().last() {
+        Some((idx, c)) if c.is_ascii_alphabetic() => {
+            let scale = match c.to_ascii_lowercase() {
+                'k' => 1_000u64,
+                'm' => 1_000_000,
+                'g' => 1_000_000_000,
+                _ => return Err(invalid("only k, m and g suffixes are supported")),
+            };
+            (&s[..idx], scale)
+        }
+        _ => (s, 1),
+    };
+    if number.starts_with('_') || number.ends_with('_') || number.contains("__") {
+        return Err(invalid("underscores only go between digits"));
+    }
+    let number = number.replace('_', "");
+    let (int, frac) = match number.split_once('.') {
+        Some((_, "")) => return Err(invalid("expect digits after the decimal point")),
+        Some((int, frac)) => (int, frac),
+        None => (number.as_str(), ""),
+    };
+    if int.is_empty() || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+        return Err(invalid(
+            "expect digits with an optional k, m or g suffix, like 10k or 1_000_000",
+        ));
+    }
+    let overflow = || invalid("too large");
+    let int = int
+        .parse::<u64>()
+        .map_err(|_| overflow())?
+        .checked_mul(scale)
+        .ok_or_else(overflow)?;
+    if frac.is_empty() {
+        return Ok(int);
+    }
+    // a fraction must not leave a partial unit, 1.5k is 1500 but 1.5 and 1.0005k are rejected
+    let unit = 10u64
+        .checked_pow(frac.len() as u32)
+        .filter(|u| *u <= scale)
+        .ok_or_else(|| invalid("not a whole number"))?;
+    let frac = frac.parse::<u64>().map_err(|_| overflow())? * (scale / unit);
+    int.checked_add(frac).ok_or_else(overflow)
+}
+
+/// parse a count-like argument which may be negative, e.g. `-10k`
+fn parse_signed_count(s: &str) -> std::result::Result<i64, String> {
+    let (negative, count) = match s.strip_prefix('-') {
+        Some(count) => (true, count),
+        None => (false, s),
+    };
+    let count = i64::try_from(parse_count(count)?).map_err(|_| format!("Invalid count {}, too large", s))?;
+    Ok(if negative { -count } else { count })
+}
+
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about=None)]
+#[clap(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "Counts and amounts accept underscores and k, m, g suffixes: 1_000_000, 10k, 1.5m"
+)]
 pub(crate) struct Cli {
     #[clap(subcommand)]
     pub(crate) command: Option<Commands>,
+
+    /// seconds to establish a connection to a node, it only limits connecting, the whole http request
+    /// is limited by the --timeout of the command, 3 seconds for commands without one
+    #[clap(long, global = true)]
+    pub(crate) node_timeout: Option<u64>,
+
+    /// rename a json-rpc method called by the tool, name=actual, e.g. eth_getBalance=findora_getBalance
+    #[clap(long, global = true)]
+    pub(crate) rpc_override: Vec<String>,
+
+    /// idle keep-alive connections kept per node, the thread pool size for test and unbounded for the
+    /// other commands by default, 0 opens a new connection for every request
+    #[clap(long, global = true, parse(try_from_str = parse_count))]
+    pub(crate) http_pool_size: Option<u64>,
+
+    /// never color the log lines, also off with NO_COLOR or when stderr is not a terminal
+    #[clap(long, global = true)]
+    pub(crate) no_color: bool,
+
+    /// only print the final summary to stdout, and errors to stderr
+    #[clap(long, global = true)]
+    pub(crate) quiet: bool,
+
+    /// node urls added to --network, one `url` or `url#weight` per line, `#` starts a comment line,
+    /// test spreads its source accounts over the nodes in proportion to their weights
+    #[clap(long, global = true, parse(from_os_str), value_name = "FILE")]
+    pub(crate) network_file: Option<PathBuf>,
+
+    /// how transaction and block hashes are printed: hex0x, hex or base64
+    #[clap(long, global = true, default_value = "hex0x")]
+    pub(crate) hash_encoding: HashEncoding,
+
+    /// randomization of the backoff of rate limited requests: full, equal or none
+    #[clap(long, global = true, default_value = "full")]
+    pub(crate) backoff_jitter: Jitter,
+}
+
+/// read the nodes of a network file, a node of weight N is listed N times
+fn read_network_file(path: &Path) -> Result<Vec<String>> {
+    let mut nodes = vec![];
+    for (idx, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |why: String| Error::Unknown(format!("invalid node at line {} of {:?}: {}", idx + 1, path, why));
+        let (url, weight) = match line.rsplit_once('#') {
+            Some((url, weight)) => (
+                url.trim(),
+                weight
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|w| *w > 0)
+                    .ok_or_else(|| invalid(format!("weight {} is not a positive integer", weight)))?,
+            ),
+            None => (line, 1),
+        };
+        // the same check as the inline list, which drops entries that are not urls
+        url::Url::parse(url).map_err(|e| invalid(format!("{} {}", url, e)))?;
+        nodes.extend(std::iter::repeat(url.to_owned()).take(weight));
+    }
+    if nodes.is_empty() {
+        return Err(Error::Unknown(format!("no node in network file {:?}", path)));
+    }
+    Ok(nodes)
 }
 
 #[allow(dead_code)]
@@ -146,32 +455,463 @@ struct BlockInfo {
     txs: u64,
     valid_txs: u64,
     block_time: Option<u64>,
-    begin: u64,
-    snapshot: u64,
-    end: u64,
-    commit: u64,
-    commit_evm: u64,
+    /// phase timings from the abcid log, none for blocks loaded from a node
+    #[serde(default)]
+    begin: Option<u64>,
+    #[serde(default)]
+    snapshot: Option<u64>,
+    #[serde(default)]
+    end: Option<u64>,
+    #[serde(default)]
+    commit: Option<u64>,
+    #[serde(default)]
+    commit_evm: Option<u64>,
+    /// app hash of the "Committed state" line, none if the height was never committed
+    #[serde(default)]
+    app_hash: Option<String>,
+    /// hash of the block, only for block infos loaded from a node
+    #[serde(default)]
+    block_hash: Option<String>,
+}
+
+/// Hashes of the current ETL source compared with the ones a previous run stored for the same heights
+#[derive(Debug, Default)]
+struct HashCheck {
+    compared: u64,
+    /// height, kind of hash, stored hash, current hash
+    mismatches: Vec<(u64, &'static str, String, String)>,
+}
+
+impl HashCheck {
+    /// compare two hashes of a height, nothing to compare if either one is unknown
+    fn compare(&mut self, height: u64, kind: &'static str, stored: Option<&String>, current: Option<&String>) {
+        if let (Some(stored), Some(current)) = (stored, current) {
+            self.compared += 1;
+            if !stored.eq_ignore_ascii_case(current) {
+                log::warn!(
+                    "{} hash of height {} differs, stored {}, now {}",
+                    kind,
+                    height,
+                    stored,
+                    current
+                );
+                self.mismatches.push((height, kind, stored.clone(), current.clone()));
+            }
+        }
+    }
+
+    /// a divergence may be a fork or corrupted data, it fails the run after everything is stored
+    fn report(&self) -> Result<()> {
+        let first = self.mismatches.iter().min_by_key(|m| m.0);
+        match first {
+            None => {
+                log::info!("{} hashes verified, no mismatch", self.compared);
+                Ok(())
+            }
+            Some((height, kind, stored, current)) => {
+                log::error!(
+                    "!!! sources diverge from height {}: {} hash stored {}, now {} !!!",
+                    height,
+                    kind,
+                    stored,
+                    current
+                );
+                Err(Error::Unknown(format!(
+                    "{} of {} hashes differ, the first divergent height is {}",
+                    self.mismatches.len(),
+                    self.compared,
+                    height
+                )))
+            }
+        }
+    }
+}
+
+/// compare the transaction counts of the stored block infos with the blocks of a node, logs losing
+/// lines count fewer transactions than the chain
+fn cross_check_txs(client: &TestClient, db: &Db, heights: Option<&Heights>, min_height: u64, max_height: u64) {
+    let (mut compared, mut unavailable) = (0u64, 0u64);
+    let mut mismatches = vec![];
+    for height in (min_height..=max_height).filter(|h| wanted(heights, *h)) {
+        let bi = match db.fetch::<BlockInfo>(height) {
+            Ok(bi) => bi,
+            Err(_) => continue,
+        };
+        let block = match client.block_with_tx_hashes_inner(BlockId::Number(height.into()), None, Some(3)) {
+            Some(block) => block,
+            None => {
+                unavailable += 1;
+                continue;
+            }
+        };
+        compared += 1;
+        let txs = block.transactions.len() as u64;
+        if txs != bi.txs {
+            log::warn!(
+                "height {} has {} transactions in the logs, {} on the node",
+                height,
+                bi.txs,
+                txs
+            );
+            mismatches.push(height);
+        }
+    }
+    if unavailable > 0 {
+        log::warn!(
+            "{} blocks are not available on the node, not cross-checked",
+            unavailable
+        );
+    }
+    if mismatches.is_empty() {
+        log::info!("{} blocks cross-checked with the node, no mismatch", compared);
+    } else {
+        let heights = mismatches.iter().map(u64::to_string).collect::<Vec<_>>();
+        log::warn!(
+            "{} of {} blocks differ in transaction count from the node, heights: {}",
+            mismatches.len(),
+            compared,
+            heights.join(",")
+        );
+    }
+}
+
+/// Heights missing from the stored block infos of a summarized range, as contiguous ranges
+#[derive(Debug, Default)]
+struct Gaps(Vec<RangeInclusive<u64>>);
+
+impl Gaps {
+    /// heights are added in increasing order
+    fn add(&mut self, height: u64) {
+        match self.0.last_mut() {
+            Some(last) if *last.end() + 1 == height => *last = *last.start()..=height,
+            _ => self.0.push(height..=height),
+        }
+    }
+
+    fn missing(&self) -> u64 {
+        self.0.iter().map(|r| r.end() - r.start() + 1).sum()
+    }
+
+    fn largest(&self) -> Option<&RangeInclusive<u64>> {
+        self.0.iter().max_by_key(|r| r.end() - r.start())
+    }
+
+    fn report(&self) {
+        if let Some(largest) = self.largest() {
+            log::warn!(
+                "{} heights missing in {} gaps, the largest one from {} to {}",
+                self.missing(),
+                self.0.len(),
+                largest.start(),
+                largest.end()
+            );
+        }
+    }
+
+    /// json or csv, by the file extension
+    fn format(path: &Path) -> Result<Format> {
+        match path.extension().and_then(|ext| ext.to_str()).map(str::parse::<Format>) {
+            Some(Ok(format)) if format != Format::Txt => Ok(format),
+            _ => Err(Error::NotSupport(format!(
+                "gap report {:?} should be a .json or .csv file",
+                path
+            ))),
+        }
+    }
+
+    /// write the gaps as json or csv, csv starts with a `#` line of the totals
+    fn write(&self, path: &Path) -> Result<()> {
+        let largest = self.largest().map(|r| (*r.start(), *r.end()));
+        let content = match Self::format(path)? {
+            Format::Json => {
+                let range = |start: u64, end: u64| json!({"start": start, "end": end, "missing": end - start + 1});
+                let report = json!({
+                    "missing": self.missing(),
+                    "largest": largest.map(|(start, end)| range(start, end)),
+                    "gaps": self.0.iter().map(|r| range(*r.start(), *r.end())).collect::<Vec<_>>(),
+                });
+                serde_json::to_string_pretty(&report).map_err(std::io::Error::from)?
+            }
+            _ => {
+                let mut content = format!(
+                    "# missing {}, largest {}\nstart,end,missing\n",
+                    self.missing(),
+                    largest.map_or_else(|| "none".to_owned(), |(start, end)| format!("{}-{}", start, end))
+                );
+                for r in &self.0 {
+                    content.push_str(&format!("{},{},{}\n", r.start(), r.end(), r.end() - r.start() + 1));
+                }
+                content
+            }
+        };
+        std::fs::write(path, content)?;
+        log::info!("{} gaps written to {:?}", self.0.len(), path);
+        Ok(())
+    }
+}
+
+/// processing phases of a block, `snapshot` is part of `begin` and `commit_evm` is part of `commit`
+const PHASES: [&str; 5] = ["begin", "snapshot", "end", "commit", "commit_evm"];
+
+/// columns of the block rows printed by the summary command
+const BLOCK_INFO_HEADER: [&str; 10] = [
+    "height",
+    "timestamp",
+    "txs",
+    "valid_txs",
+    "block_time",
+    "begin",
+    "snapshot",
+    "end",
+    "commit",
+    "commit_evm",
+];
+
+/// Columns of [`BLOCK_INFO_HEADER`] selected by `--metric-fields`
+#[derive(Debug, Clone)]
+pub struct MetricFields(Vec<usize>);
+
+impl std::str::FromStr for MetricFields {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let fields = s
+            .split(',')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(|f| {
+                output::column(&BLOCK_INFO_HEADER, f).map_err(|_| {
+                    format!(
+                        "Invalid metric field {}, expect some of {}",
+                        f,
+                        BLOCK_INFO_HEADER.join(",")
+                    )
+                })
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if fields.is_empty() {
+            return Err("Invalid metric fields, at least one is needed".to_owned());
+        }
+        Ok(Self(fields))
+    }
+}
+
+impl MetricFields {
+    fn header(&self) -> Vec<&'static str> {
+        self.0.iter().map(|i| BLOCK_INFO_HEADER[*i]).collect()
+    }
+
+    /// the selected values of a [`BlockInfo::row`]
+    fn select(&self, row: &[serde_json::Value]) -> Vec<serde_json::Value> {
+        self.0.iter().map(|i| row[*i].clone()).collect()
+    }
+}
+
+impl BlockInfo {
+    /// timings of [`PHASES`]
+    fn phases(&self) -> [Option<u64>; 5] {
+        [self.begin, self.snapshot, self.end, self.commit, self.commit_evm]
+    }
+
+    /// values of [`BLOCK_INFO_HEADER`]
+    fn row(&self) -> Vec<serde_json::Value> {
+        let mut row = vec![
+            json!(self.height),
+            json!(self.timestamp),
+            json!(self.txs),
+            json!(self.valid_txs),
+            json!(self.block_time),
+        ];
+        row.extend(self.phases().iter().map(|t| json!(t)));
+        row
+    }
 }
 
 impl Display for BlockInfo {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let block_time = self.block_time.unwrap_or(0);
-        write!(
-            f,
-            "{},{},{},{},{},{},{},{}",
-            self.height, block_time, self.txs, self.begin, self.snapshot, self.end, self.commit, self.commit_evm
+        let phases = self
+            .phases()
+            .iter()
+            .map(|t| t.map_or_else(String::new, |t| t.to_string()))
+            .collect::<Vec<_>>();
+        write!(f, "{},{},{},{}", self.height, block_time, self.txs, phases.join(","))
+    }
+}
+
+/// path of a log which is read from stdin
+const STDIN_PATH: &str = "-";
+
+/// transactions a block could hold at most, far above any real block, counts beyond it are corrupted
+const MAX_BLOCK_TXS: u64 = 10_000_000;
+
+/// transactions of an executed block, none for a count beyond `MAX_BLOCK_TXS`
+fn block_txs(valid: u64, invalid: u64) -> Option<u64> {
+    valid.checked_add(invalid).filter(|txs| *txs <= MAX_BLOCK_TXS)
+}
+
+fn is_stdin<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref() == Path::new(STDIN_PATH)
+}
+
+/// open a log file, `-` means reading from stdin
+fn open_log<P: AsRef<Path>>(path: P, max_line_bytes: usize) -> Result<LogLines> {
+    let reader: Box<dyn BufRead> = if is_stdin(&path) {
+        Box::new(std::io::BufReader::new(std::io::stdin()))
+    } else {
+        Box::new(std::io::BufReader::new(std::fs::File::open(path)?))
+    };
+    Ok(LogLines {
+        reader,
+        max_line_bytes,
+        skipped: 0,
+        number: 0,
+    })
+}
+
+/// Lines of a log, a line longer than `max_line_bytes` is skipped instead of buffered
+struct LogLines {
+    reader: Box<dyn BufRead>,
+    max_line_bytes: usize,
+    /// lines skipped for their length
+    skipped: u64,
+    /// number of the last line read, skipped ones included
+    number: u64,
+}
+
+impl Iterator for LogLines {
+    type Item = std::io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = Vec::new();
+        let mut too_long = false;
+        loop {
+            let (used, found) = {
+                let buf = match self.reader.fill_buf() {
+                    Ok(buf) => buf,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Some(Err(e)),
+                };
+                if buf.is_empty() {
+                    break;
+                }
+                let (end, found) = match buf.iter().position(|b| *b == b'\n') {
+                    Some(end) => (end, true),
+                    None => (buf.len(), false),
+                };
+                if !too_long && line.len() + end > self.max_line_bytes {
+                    too_long = true;
+                    line = Vec::new();
+                } else if !too_long {
+                    line.extend_from_slice(&buf[..end]);
+                }
+                (end + found as usize, found)
+            };
+            self.reader.consume(used);
+            if found && too_long {
+                self.skip_line();
+                too_long = false;
+            } else if found {
+                return Some(self.text(line));
+            }
+        }
+        // the last line has no line break
+        if too_long {
+            self.skip_line();
+        }
+        (!line.is_empty()).then(|| self.text(line))
+    }
+}
+
+impl LogLines {
+    fn skip_line(&mut self) {
+        self.number += 1;
+        self.skipped += 1;
+        log::warn!("skipped a log line longer than {} bytes", self.max_line_bytes);
+    }
+
+    fn text(&mut self, mut line: Vec<u8>) -> std::io::Result<String> {
+        self.number += 1;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        String::from_utf8(line).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Heights selected by a `--heights-file` and the `--min-height`/`--max-height` clamps
+#[derive(Debug, Default)]
+struct Heights(Vec<RangeInclusive<u64>>);
+
+impl Heights {
+    /// read heights separated by commas or whitespaces, each one is a height or a `start-end` range,
+    /// `#` starts a comment
+    fn load<P: AsRef<Path> + std::fmt::Debug>(path: P) -> Result<Self> {
+        let mut ranges = vec![];
+        for line in std::fs::read_to_string(&path)?.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            for word in line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|w| !w.is_empty())
+            {
+                let range = match word.split_once('-') {
+                    Some((start, end)) => start.trim().parse::<u64>().ok().zip(end.trim().parse::<u64>().ok()),
+                    None => word.parse::<u64>().ok().map(|h| (h, h)),
+                };
+                match range {
+                    Some((start, end)) if start <= end => ranges.push(start..=end),
+                    _ => return Err(Error::Unknown(format!("invalid height {} in {:?}", word, path))),
+                }
+            }
+        }
+        Ok(Self(ranges))
+    }
+
+    fn contains(&self, height: u64) -> bool {
+        self.0.iter().any(|r| r.contains(&height))
+    }
+
+    /// keep only the heights within `min..=max`
+    fn clamp(self, min: u64, max: u64) -> Self {
+        Self(
+            self.0
+                .into_iter()
+                .filter_map(|r| {
+                    let (start, end) = ((*r.start()).max(min), (*r.end()).min(max));
+                    (start <= end).then(|| start..=end)
+                })
+                .collect(),
         )
     }
+
+    /// the lowest and highest selected heights
+    fn bounds(&self) -> Option<(u64, u64)> {
+        let min = self.0.iter().map(|r| *r.start()).min()?;
+        let max = self.0.iter().map(|r| *r.end()).max()?;
+        Some((min, max))
+    }
+}
+
+/// whether a height is selected, all heights are selected without a heights file
+fn wanted(heights: Option<&Heights>, height: u64) -> bool {
+    heights.map_or(true, |hs| hs.contains(height))
 }
 
 #[allow(unused)]
-fn parse_abcid<P>(abcid: P, db: Rc<Db>) -> Result<()>
+fn parse_abcid<P>(abcid: P, db: Rc<Db>, heights: Option<&Heights>, max_line_bytes: usize) -> Result<()>
 where
     P: AsRef<Path> + std::fmt::Debug,
 {
-    let abci_log = std::fs::File::open(abcid)?;
-    std::io::BufReader::new(abci_log)
-        .lines()
+    let fetch = |height| {
+        if wanted(heights, height) {
+            db.fetch::<BlockInfo>(height).ok()
+        } else {
+            None
+        }
+    };
+    let mut lines = open_log(&abcid, max_line_bytes)?;
+    lines
+        .by_ref()
         .filter_map(|line| line.map_or(None, |l| if l.contains("tps,") { Some(l) } else { None }))
         .for_each(|line| {
             let words = line[52..].split(',').collect::<Vec<_>>();
@@ -181,13 +921,10 @@ where
                     let height = words[words.len() - 2].split_whitespace().collect::<Vec<_>>()[1]
                         .parse::<u64>()
                         .unwrap();
-                    if let Ok(raw_bi) = db.get(height) {
-                        let mut bi: BlockInfo = serde_json::from_str(raw_bi.as_str()).unwrap();
-                        bi.snapshot = words[2].parse::<u64>().unwrap();
-                        bi.begin = words[3].parse::<u64>().unwrap();
-                        let new_raw = serde_json::to_string(&bi).unwrap();
-                        db.insert(bi.height, new_raw.as_bytes())
-                            .expect("failed to update a block info");
+                    if let Some(mut bi) = fetch(height) {
+                        bi.snapshot = Some(words[2].parse::<u64>().unwrap());
+                        bi.begin = Some(words[3].parse::<u64>().unwrap());
+                        db.put(bi.height, &bi).expect("failed to update a block info");
                     }
                 }
                 Some("end of end_block") => {
@@ -195,12 +932,9 @@ where
                     let height = words[words.len() - 2].split_whitespace().collect::<Vec<_>>()[1]
                         .parse::<u64>()
                         .unwrap();
-                    if let Ok(raw_bi) = db.get(height) {
-                        let mut bi: BlockInfo = serde_json::from_str(raw_bi.as_str()).unwrap();
-                        bi.end = words[2].parse::<u64>().unwrap();
-                        let new_raw = serde_json::to_string(&bi).unwrap();
-                        db.insert(bi.height, new_raw.as_bytes())
-                            .expect("failed to update a block info");
+                    if let Some(mut bi) = fetch(height) {
+                        bi.end = Some(words[2].parse::<u64>().unwrap());
+                        db.put(bi.height, &bi).expect("failed to update a block info");
                     }
                 }
                 Some("end of commit") => {
@@ -208,132 +942,977 @@ where
                     let height = words[words.len() - 2].split_whitespace().collect::<Vec<_>>()[1]
                         .parse::<u64>()
                         .unwrap();
-                    if let Ok(raw_bi) = db.get(height) {
-                        let mut bi: BlockInfo = serde_json::from_str(raw_bi.as_str()).unwrap();
-                        bi.commit_evm = words[3].parse::<u64>().unwrap();
-                        bi.commit = words[4].parse::<u64>().unwrap();
-                        let new_raw = serde_json::to_string(&bi).unwrap();
-                        db.insert(bi.height, new_raw.as_bytes())
-                            .expect("failed to update a block info");
+                    if let Some(mut bi) = fetch(height) {
+                        bi.commit_evm = Some(words[3].parse::<u64>().unwrap());
+                        bi.commit = Some(words[4].parse::<u64>().unwrap());
+                        db.put(bi.height, &bi).expect("failed to update a block info");
                     }
                 }
                 _ => {}
             }
         });
+    if lines.skipped > 0 {
+        log::warn!("{} overlong lines skipped in {:?}", lines.skipped, abcid);
+    }
     Ok(())
 }
 
-fn parse_tendermint<P>(tendermint: P, db: Rc<Db>) -> Result<(u64, u64)>
+fn parse_tendermint<P>(
+    tendermint: P,
+    db: Rc<Db>,
+    on_duplicate: OnDuplicate,
+    heights: Option<&Heights>,
+    strict_timestamps: bool,
+    mut check: Option<&mut HashCheck>,
+    max_line_bytes: usize,
+) -> Result<(u64, u64)>
 where
     P: AsRef<Path> + std::fmt::Debug,
 {
+    // app hashes stored before this run, kept as the executed blocks overwrite them
+    let mut stored_hashes = HashMap::new();
     let mut min_height = u64::MAX;
     let mut max_height = u64::MIN;
-    let tm_log = std::fs::File::open(tendermint)?;
-    for line in std::io::BufReader::new(tm_log).lines() {
+    let mut seen = HashSet::new();
+    let mut committed = HashSet::new();
+    let mut duplicates = 0u64;
+    let mut non_monotonic = 0u64;
+    let mut bad_timestamps = 0u64;
+    let mut bad_txs = 0u64;
+    let (mut stored_bytes, mut json_bytes) = (0usize, 0usize);
+    let mut lines = open_log(&tendermint, max_line_bytes)?;
+    while let Some(line) = lines.next() {
         match line {
             Ok(l) if l.contains("Executed block") => {
-                let mut blk = (None, None, None, None);
+                let mut blk = (None, None, None);
                 // I[2022-04-07|02:17:07.759] Executed block module=state height=191 validTxs=3368 invalidTxs=666
                 // parse timestamp
                 // %Y-%m-%d|%H:%M:%S.%.3f
-                let time_str = &l[2..25];
-                blk.0 = NaiveDateTime::parse_from_str(time_str, "%Y-%m-%d|%H:%M:%S%.3f")
-                    .map(|dt| dt.timestamp())
-                    .ok();
+                let timestamp = match l
+                    .get(2..25)
+                    .and_then(|time_str| NaiveDateTime::parse_from_str(time_str, "%Y-%m-%d|%H:%M:%S%.3f").ok())
+                {
+                    Some(dt) => dt.timestamp(),
+                    None if strict_timestamps => {
+                        return Err(Error::LogParse {
+                            line: lines.number,
+                            reason: format!("unparseable timestamp in {}", l),
+                        });
+                    }
+                    None => {
+                        bad_timestamps += 1;
+                        log::warn!("skipped a line with an unparseable timestamp: {}", l);
+                        continue;
+                    }
+                };
                 for word in l.split_whitespace() {
                     let kv = word.split('=').collect::<Vec<_>>();
                     if kv.len() != 2 {
                         continue;
                     } else {
                         match kv[0] {
-                            "height" => blk.1 = kv[1].parse::<u64>().ok(),
-                            "validTxs" => blk.2 = kv[1].parse::<u64>().ok(),
-                            "invalidTxs" => blk.3 = kv[1].parse::<u64>().ok(),
+                            "height" => blk.0 = kv[1].parse::<u64>().ok(),
+                            "validTxs" => blk.1 = kv[1].parse::<u64>().ok(),
+                            "invalidTxs" => blk.2 = kv[1].parse::<u64>().ok(),
                             _ => {}
                         }
                     }
                 }
-                let bi = BlockInfo {
-                    height: blk.1.unwrap(),
-                    timestamp: blk.0.unwrap(),
-                    txs: blk.2.unwrap() + blk.3.unwrap(),
-                    valid_txs: blk.2.unwrap(),
+                let (height, valid, invalid) = match blk {
+                    (Some(height), Some(valid), Some(invalid)) => (height, valid, invalid),
+                    _ => {
+                        return Err(Error::LogParse {
+                            line: lines.number,
+                            reason: format!("executed block without height, validTxs or invalidTxs in {}", l),
+                        });
+                    }
+                };
+                let txs = match block_txs(valid, invalid) {
+                    Some(txs) => txs,
+                    None => {
+                        bad_txs += 1;
+                        log::warn!("skipped a line with an implausible transaction count: {}", l);
+                        continue;
+                    }
+                };
+                let mut bi = BlockInfo {
+                    height,
+                    timestamp,
+                    txs,
+                    valid_txs: valid,
                     ..Default::default()
                 };
+                if !wanted(heights, bi.height) {
+                    continue;
+                }
+                if let Some(prev) = bi.height.checked_sub(1).and_then(|h| db.fetch::<BlockInfo>(h).ok()) {
+                    if bi.timestamp >= prev.timestamp {
+                        bi.block_time = Some((bi.timestamp - prev.timestamp) as u64);
+                    } else {
+                        non_monotonic += 1;
+                        log::warn!(
+                            "timestamp of height {} is {} seconds earlier than the previous height, block time unknown",
+                            bi.height,
+                            prev.timestamp - bi.timestamp
+                        );
+                    }
+                }
+                if check.is_some() {
+                    stored_hashes
+                        .entry(bi.height)
+                        .or_insert_with(|| db.fetch::<BlockInfo>(bi.height).ok().and_then(|s| s.app_hash));
+                }
+                if !seen.insert(bi.height) {
+                    duplicates += 1;
+                    match on_duplicate {
+                        OnDuplicate::First => continue,
+                        OnDuplicate::Last => {}
+                        OnDuplicate::Warn => {
+                            let prev = db.fetch::<BlockInfo>(bi.height).ok();
+                            log::warn!(
+                                "height {} executed again, validTxs {:?} -> {}, keeping the last one",
+                                bi.height,
+                                prev.map(|p| p.valid_txs),
+                                bi.valid_txs
+                            );
+                        }
+                    }
+                }
                 if min_height > bi.height {
                     min_height = bi.height;
                 }
                 if max_height < bi.height {
                     max_height = bi.height
                 }
-                let raw_data = serde_json::to_string(&bi).unwrap();
-                db.insert(bi.height, raw_data.as_bytes())
-                    .expect("failed to insert a block info");
+                stored_bytes += db.put(bi.height, &bi)?;
+                json_bytes += serde_json::to_vec(&bi).map_or(0, |raw| raw.len());
                 //blocks.insert(bi.height, std::cell::RefCell::new(bi));
             }
+            Ok(l) if l.contains("Committed state") => {
+                // I[2022-04-07|02:17:07.800] Committed state module=state height=191 txs=4034 appHash=5E4B...
+                let (mut height, mut app_hash) = (None, None);
+                for word in l.split_whitespace() {
+                    match word.split_once('=') {
+                        Some(("height", v)) => height = v.parse::<u64>().ok(),
+                        Some(("appHash", v)) => app_hash = Some(v.to_owned()),
+                        _ => {}
+                    }
+                }
+                let height = match height {
+                    Some(h) if wanted(heights, h) => h,
+                    _ => continue,
+                };
+                committed.insert(height);
+                if let Some(check) = check.as_deref_mut() {
+                    let stored = stored_hashes.get(&height).cloned().flatten();
+                    check.compare(height, "app", stored.as_ref(), app_hash.as_ref());
+                }
+                match db.fetch::<BlockInfo>(height) {
+                    Ok(mut bi) => {
+                        if bi.app_hash.is_some() && bi.app_hash != app_hash {
+                            log::warn!(
+                                "height {} committed again with app hash {:?}, was {:?}",
+                                height,
+                                app_hash,
+                                bi.app_hash
+                            );
+                        }
+                        bi.app_hash = app_hash;
+                        db.put(bi.height, &bi)?;
+                    }
+                    Err(_) => log::debug!("height {} committed without an executed block", height),
+                }
+            }
             _ => {}
         }
     }
-    Ok((min_height, max_height))
-}
-
-impl Cli {
-    pub(crate) fn parse_args() -> Self {
-        Cli::parse()
+    if duplicates > 0 {
+        log::warn!("{} duplicate heights found, policy {:?}", duplicates, on_duplicate);
     }
-
-    pub(crate) fn etl_cmd<P>(abcid: &Option<P>, tendermint: &Option<P>, redis: &str, load: bool) -> Result<()>
-    where
-        P: AsRef<Path> + std::fmt::Debug,
-    {
-        log::info!("{:?} {:?} {} {}", abcid, tendermint, redis, load);
-
-        let proto = if &redis[..4] == "unix" { Proto::Unix } else { Proto::Url };
-        let db = Rc::new(Db::new(Some(proto), None, redis, Some(6379), Some(0))?);
-
-        let (min_height, max_height) = tendermint.as_ref().map_or_else(
-            || (u64::MAX, u64::MIN),
-            |tendermint| parse_tendermint(tendermint, db.clone()).unwrap_or((u64::MAX, u64::MIN)),
+    if bad_timestamps > 0 {
+        log::warn!(
+            "{} executed blocks skipped for their unparseable timestamps",
+            bad_timestamps
         );
-        abcid.as_ref().map(|abcid| parse_abcid(abcid, db.clone()));
-
-        for h in min_height..=max_height {
-            if let Ok(bi) = db.get(h) {
-                let bi = serde_json::from_str::<BlockInfo>(bi.as_str()).unwrap();
-                let last_bi = {
-                    if h == 0 {
-                        None
-                    } else if let Ok(bi) = db.get(h - 1) {
-                        serde_json::from_str::<BlockInfo>(bi.as_str()).ok()
-                    } else {
-                        None
-                    }
-                };
-
-                let (block_time, tps) = match last_bi {
-                    Some(last) if bi.timestamp > last.timestamp => {
-                        let time = bi.timestamp - last.timestamp;
-                        let tps = bi.txs as f64 / time as f64;
-                        (time, tps)
-                    }
-                    _ => (0i64, 0f64),
-                };
-                log::info!("{},{},{},{},{:.3}", bi.height, bi.txs, bi.valid_txs, block_time, tps,);
-            }
+    }
+    if lines.skipped > 0 {
+        log::warn!("{} overlong lines skipped in {:?}", lines.skipped, tendermint);
+    }
+    if bad_txs > 0 {
+        log::warn!(
+            "{} executed blocks skipped for more than {} transactions",
+            bad_txs,
+            MAX_BLOCK_TXS
+        );
+    }
+    if non_monotonic > 0 {
+        log::warn!(
+            "{} heights have a timestamp earlier than the previous height",
+            non_monotonic
+        );
+    }
+    // older tendermint versions never log committed states
+    if !committed.is_empty() {
+        let mut uncommitted = seen.difference(&committed).copied().collect::<Vec<_>>();
+        uncommitted.sort_unstable();
+        if !uncommitted.is_empty() {
+            log::warn!(
+                "{} executed heights never committed, the node may have crashed: {:?}",
+                uncommitted.len(),
+                uncommitted
+            );
         }
-        Ok(())
     }
-
-    pub(crate) fn profiler(network: &str, enabled: bool) -> Result<()> {
-        let url = format!("{}/configuration", network);
-        profiler::set_profiler(url.as_str(), enabled)
+    if json_bytes > 0 {
+        log::info!(
+            "stored {} bytes with {} codec, json takes {} bytes, saved {:.1}%",
+            stored_bytes,
+            db.codec(),
+            json_bytes,
+            (1f64 - stored_bytes as f64 / json_bytes as f64) * 100f64
+        );
     }
+    Ok((min_height, max_height))
 }
 
-#[derive(Subcommand, Debug)]
-pub enum Commands {
-    /// Fund Ethereum accounts
+/// synthesize block infos from the blocks of a node, the abcid phase timings are left empty
+fn load_rpc(
+    client: &TestClient,
+    db: Rc<Db>,
+    heights: Option<&Heights>,
+    start: u64,
+    end: u64,
+    mut check: Option<&mut HashCheck>,
+) -> Result<(u64, u64)> {
+    let mut min_height = u64::MAX;
+    let mut max_height = u64::MIN;
+    let mut prev: Option<BlockInfo> = None;
+    for height in (start..=end).filter(|h| wanted(heights, *h)) {
+        let block = client
+            .block_with_tx_hashes_inner(BlockId::Number(height.into()), None, Some(3))
+            .ok_or_else(|| Error::NodeUnreachable(format!("block {} is not available on the node", height)))?;
+        let txs = block.transactions.len() as u64;
+        let mut bi = BlockInfo {
+            height,
+            timestamp: block.timestamp.as_u64() as i64,
+            // only executed transactions make it into a block
+            txs,
+            valid_txs: txs,
+            block_hash: block.hash.map(|h| format!("{:?}", h)),
+            ..Default::default()
+        };
+        if let Some(check) = check.as_deref_mut() {
+            let stored = db.fetch::<BlockInfo>(height).ok().and_then(|s| s.block_hash);
+            check.compare(height, "block", stored.as_ref(), bi.block_hash.as_ref());
+        }
+        // the previous height may be skipped by the selection, or stored by an earlier run
+        let prev_bi = match prev.take() {
+            Some(p) if p.height + 1 == height => Some(p),
+            _ => height.checked_sub(1).and_then(|h| db.fetch::<BlockInfo>(h).ok()),
+        };
+        if let Some(p) = prev_bi.filter(|p| bi.timestamp >= p.timestamp) {
+            bi.block_time = Some((bi.timestamp - p.timestamp) as u64);
+        }
+        db.put(bi.height, &bi)?;
+        min_height = min_height.min(height);
+        max_height = max_height.max(height);
+        if (height - start + 1) % 1000 == 0 {
+            log::info!(
+                "{}/{} heights loaded from the node",
+                height - start + 1,
+                end - start + 1
+            );
+        }
+        prev = Some(bi);
+    }
+    Ok((min_height, max_height))
+}
+
+/// Redis server address given by `--redis`
+#[derive(Debug, PartialEq, Eq)]
+struct RedisAddr {
+    /// hostname or ip, ipv6 literals are kept in brackets as needed by the connection url
+    host: String,
+    port: u32,
+    db: u8,
+}
+
+impl std::str::FromStr for RedisAddr {
+    type Err = String;
+
+    /// `host[:port][/db]`, the host is a hostname, an ipv4 or an ipv6 literal like `[::1]`,
+    /// a bare ipv6 literal like `::1` is accepted when there is no port
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        const PORT: u32 = 6379;
+        let invalid = || format!("Invalid redis address {}, expect host[:port][/db]", s);
+        let s = s.trim().trim_start_matches("redis://");
+        let (addr, db) = match s.rsplit_once('/') {
+            Some((addr, db)) => (addr, db.parse::<u8>().map_err(|_| invalid())?),
+            None => (s, 0),
+        };
+        let (host, port) = if let Some(rest) = addr.strip_prefix('[') {
+            let (ip, rest) = rest.split_once(']').ok_or_else(invalid)?;
+            let port = match rest.strip_prefix(':') {
+                Some(port) => port.parse::<u16>().map_err(|_| invalid())?.into(),
+                None if rest.is_empty() => PORT,
+                None => return Err(invalid()),
+            };
+            (format!("[{}]", ip), port)
+        } else if addr.matches(':').count() > 1 {
+            (format!("[{}]", addr), PORT)
+        } else {
+            match addr.split_once(':') {
+                Some((host, port)) => (host.to_owned(), port.parse::<u16>().map_err(|_| invalid())?.into()),
+                None => (addr.to_owned(), PORT),
+            }
+        };
+        if host.is_empty() || host == "[]" {
+            return Err(invalid());
+        }
+        Ok(Self { host, port, db })
+    }
+}
+
+/// Redis databases available when the server does not let us read its config
+const REDIS_DATABASES: u64 = 16;
+
+/// connect to `redis`, `port` and `db` override the ones given in the address,
+/// a `unix:<path>` address is a socket which takes its db from `db` only
+fn connect_db(redis: &str, port: Option<u32>, db: Option<u8>, codec: Codec) -> Result<Db> {
+    let (conn, server, index) = match redis.strip_prefix("unix:") {
+        Some(path) => {
+            let path = path.strip_prefix("//").unwrap_or(path);
+            if port.is_some() {
+                return Err(Error::NotSupport(format!(
+                    "--redis-port does not apply to the unix socket {}",
+                    path
+                )));
+            }
+            let index = db.unwrap_or_default();
+            let conn = Db::new(Some(Proto::Unix), None, path, None, Some(index), Some(codec));
+            (conn, format!("socket {}", path), index)
+        }
+        None => {
+            let addr = redis.parse::<RedisAddr>().map_err(Error::Unknown)?;
+            let (host, port, index) = (addr.host, port.unwrap_or(addr.port), db.unwrap_or(addr.db));
+            let conn = Db::new(Some(Proto::Url), None, &host, Some(port), Some(index), Some(codec));
+            (conn, format!("host {}, port {}", host, port), index)
+        }
+    };
+    let db = conn.with_context(|| format!("while connecting to redis {}", redis))?;
+    db.ping().map_err(|e| {
+        let reason = match e {
+            Error::Db(e) => e.to_string(),
+            e => e.to_string(),
+        };
+        Error::Unavailable(format!(
+            "redis is unavailable at {} ({}), please check the redis service is running, \
+             or use `--store file:<path>` to store block infos without redis",
+            server, reason
+        ))
+    })?;
+    let databases = db.databases()?.unwrap_or(REDIS_DATABASES);
+    if u64::from(index) >= databases {
+        return Err(Error::NotSupport(format!(
+            "redis db {} is out of range, the server at {} has {} databases (0-{})",
+            index,
+            server,
+            databases,
+            databases.saturating_sub(1)
+        )));
+    }
+    Ok(db)
+}
+
+/// write and read back a sentinel, an eviction policy other than `noeviction` may silently drop
+/// block infos once redis is full
+fn preflight_store(db: &Db, store: &Store) -> Result<()> {
+    let round_trip = db
+        .check_write()
+        .with_context(|| format!("while checking writes to {}", db))?;
+    log::info!("store {} write checked, round trip {:?}", db, round_trip);
+    if let Store::File(_) = store {
+        return Ok(());
+    }
+    match db.eviction_policy()? {
+        Some(policy) if policy != "noeviction" => log::warn!(
+            "!!! redis maxmemory-policy is {}, block infos may be evicted when memory is full !!!",
+            policy
+        ),
+        Some(_) => {}
+        None => log::warn!("cannot read the redis maxmemory-policy, evictions are not ruled out"),
+    }
+    Ok(())
+}
+
+fn open_store(store: &Store, redis: &str, port: Option<u32>, db: Option<u8>, codec: Codec) -> Result<Db> {
+    match store {
+        Store::Redis => connect_db(redis, port, db, codec),
+        Store::File(path) => {
+            Db::open_file(path, Some(codec)).with_context(|| format!("while opening file store {:?}", path))
+        }
+    }
+}
+
+/// keep the height range of the store covering `min..=max`, later runs only extend it
+fn store_bounds(db: &Db, min: u64, max: u64) -> Result<()> {
+    if min > max {
+        return Ok(());
+    }
+    let (min, max) = match stored_bounds(db)? {
+        Some((stored_min, stored_max)) => (min.min(stored_min), max.max(stored_max)),
+        None => (min, max),
+    };
+    db.set_meta("min_height", &min.to_string())?;
+    db.set_meta("max_height", &max.to_string())?;
+    log::info!("stored heights range from {} to {}", min, max);
+    Ok(())
+}
+
+/// height range of the store written by ETL, `None` if no run stored it
+fn stored_bounds(db: &Db) -> Result<Option<(u64, u64)>> {
+    let bound = |name: &str| -> Result<Option<u64>> {
+        db.get_meta(name)?
+            .map(|value| {
+                value
+                    .parse::<u64>()
+                    .map_err(|e| Error::Codec(format!("invalid {} {}: {}", name, value, e)))
+            })
+            .transpose()
+    };
+    Ok(bound("min_height")?.zip(bound("max_height")?))
+}
+
+/// print the mean and p95 of every phase, and its share of the whole block processing (begin + end + commit)
+fn print_phases(phases: &[Summary]) {
+    let total = phases
+        .iter()
+        .filter(|s| ["begin", "end", "commit"].contains(&s.name()))
+        .map(Summary::mean)
+        .sum::<f64>();
+    if total <= 0f64 {
+        return;
+    }
+    report::summary("phase,mean,p95,share");
+    for s in phases {
+        report::summary(format!(
+            "{},{:.3},{:.3},{:.1}%",
+            s.name(),
+            s.mean(),
+            s.quantiles()[1],
+            s.mean() / total * 100f64
+        ));
+    }
+}
+
+/// print per-block rows and aggregate statistics of the stored block infos in a height range,
+/// with `tps_window` the rows end with the TPS over the trailing seconds up to the block,
+/// selected heights without a stored block info are returned as gaps, `fields` picks the columns of the rows
+#[allow(clippy::too_many_arguments)]
+fn summarize(
+    db: &Db,
+    min_height: u64,
+    max_height: u64,
+    approx_quantiles: bool,
+    rows: Option<u64>,
+    exclude_zero_tx: bool,
+    heights: Option<&Heights>,
+    tps_window: Option<u64>,
+    fields: Option<&MetricFields>,
+) -> (Vec<Summary>, Gaps) {
+    // a row per height when asked for, buffered and flushed every `rows` rows, none in quiet mode
+    let mut out = rows
+        .filter(|_| !report::quiet())
+        .map(|every| (BufWriter::new(std::io::stdout()), every.max(1)));
+    let (mut printed, printing) = (0u64, Instant::now());
+    // flushes and the time spent in them, to tell what flushing every row would cost
+    let (mut flushes, mut flushing) = (0u64, Duration::ZERO);
+    if let (Some((writer, _)), Some(fields)) = (out.as_mut(), fields) {
+        let rolling = if tps_window.is_some() { ",rolling_tps" } else { "" };
+        if let Err(e) = writeln!(writer, "{}{}", fields.header().join(","), rolling) {
+            log::warn!("stopped printing rows: {}", e);
+            out = None;
+        }
+    }
+    let mut txs_summary = Summary::new("txs", approx_quantiles);
+    let mut block_time_summary = Summary::new("block_time", approx_quantiles);
+    let mut tps_summary = Summary::new("tps", approx_quantiles);
+    let mut non_monotonic = 0u64;
+    let mut gaps = Gaps::default();
+    // timestamps and transactions of the blocks inside the rolling window, and their sum
+    let mut window = VecDeque::<(i64, u64)>::new();
+    let mut window_txs = 0u64;
+    let mut phase_summaries = PHASES
+        .iter()
+        .map(|p| Summary::new(p, approx_quantiles))
+        .collect::<Vec<_>>();
+    for h in (min_height..=max_height).filter(|h| wanted(heights, *h)) {
+        let bi = match db.fetch::<BlockInfo>(h) {
+            Ok(bi) => bi,
+            Err(_) => {
+                gaps.add(h);
+                continue;
+            }
+        };
+        let last_bi = if h == 0 {
+            None
+        } else {
+            db.fetch::<BlockInfo>(h - 1).ok()
+        };
+
+        if matches!(&last_bi, Some(last) if bi.timestamp < last.timestamp) {
+            non_monotonic += 1;
+        }
+        let (block_time, tps) = match last_bi {
+            Some(last) if bi.timestamp > last.timestamp => {
+                let time = bi.timestamp - last.timestamp;
+                let tps = bi.txs as f64 / time as f64;
+                (time, tps)
+            }
+            _ => (0i64, 0f64),
+        };
+        let rolling = tps_window.map(|secs| {
+            window.push_back((bi.timestamp, bi.txs));
+            window_txs += bi.txs;
+            while let Some((timestamp, txs)) = window.front().copied() {
+                if timestamp > bi.timestamp - secs as i64 {
+                    break;
+                }
+                window.pop_front();
+                window_txs -= txs;
+            }
+            format!(",{:.3}", window_txs as f64 / secs as f64)
+        });
+        if let Some((writer, every)) = out.as_mut() {
+            let row = match fields {
+                Some(fields) => output::line(&fields.select(&bi.row())),
+                None => format!("{},{},{},{},{:.3}", bi.height, bi.txs, bi.valid_txs, block_time, tps),
+            };
+            let written = writeln!(writer, "{}{}", row, rolling.unwrap_or_default()).and_then(|_| {
+                printed += 1;
+                if printed % *every == 0 {
+                    let begin = Instant::now();
+                    let flushed = writer.flush();
+                    flushes += 1;
+                    flushing += begin.elapsed();
+                    flushed
+                } else {
+                    Ok(())
+                }
+            });
+            // e.g. a closed pipe, the summaries are still worth computing
+            if let Err(e) = written {
+                log::warn!("stopped printing rows: {}", e);
+                out = None;
+            }
+        }
+        if exclude_zero_tx && bi.txs == 0 {
+            continue;
+        }
+        txs_summary.add(bi.txs as f64);
+        // blocks without abcid timings would drag the phase means down, older records store them as zeros
+        if bi.phases().iter().flatten().any(|t| *t > 0) {
+            phase_summaries
+                .iter_mut()
+                .zip(bi.phases())
+                .filter_map(|(s, t)| t.map(|t| (s, t)))
+                .for_each(|(s, t)| s.add(t as f64));
+        }
+        if block_time > 0 {
+            block_time_summary.add(block_time as f64);
+            tps_summary.add(tps);
+        }
+    }
+    if let Some((mut writer, every)) = out {
+        if let Err(e) = writer.flush() {
+            log::warn!("failed to print the last rows: {}", e);
+        }
+        let secs = printing.elapsed().as_secs_f64();
+        log::info!(
+            "{} rows printed in {:.3}s, {:.0} rows/s, flushed every {} rows",
+            printed,
+            secs,
+            printed as f64 / secs.max(f64::EPSILON),
+            every
+        );
+        if flushes > 0 && every > 1 {
+            // every row not followed by a flush would have paid the mean flush time
+            let per_flush = flushing.as_secs_f64() / flushes as f64;
+            let per_row_secs = secs + (printed - flushes) as f64 * per_flush;
+            log::info!(
+                "{} flushes took {:.3}s, {:.3} ms each, flushing every row would take about {:.3}s, {:.0} rows/s",
+                flushes,
+                flushing.as_secs_f64(),
+                per_flush * 1000f64,
+                per_row_secs,
+                printed as f64 / per_row_secs.max(f64::EPSILON)
+            );
+        }
+    }
+    report::summary(Summary::header(approx_quantiles));
+    report::summary(&txs_summary);
+    report::summary(&block_time_summary);
+    report::summary(&tps_summary);
+    report::summary(format!("non-monotonic timestamps,{}", non_monotonic));
+    print_phases(&phase_summaries);
+    gaps.report();
+    (vec![txs_summary, block_time_summary, tps_summary], gaps)
+}
+
+impl Cli {
+    pub(crate) fn parse_args() -> Self {
+        Cli::parse()
+    }
+
+    /// append the nodes of --network-file to the --network of the command
+    pub(crate) fn merge_network_file(&mut self) -> Result<()> {
+        let path = match &self.network_file {
+            Some(path) => path.clone(),
+            None => return Ok(()),
+        };
+        let nodes = read_network_file(&path)?;
+        match &mut self.command {
+            Some(Commands::Fund { network, .. })
+            | Some(Commands::Info { network, .. })
+            | Some(Commands::Watch { network, .. })
+            | Some(Commands::EstimateGas { network, .. })
+            | Some(Commands::Logs { network, .. })
+            | Some(Commands::Transaction { network, .. })
+            | Some(Commands::Block { network, .. })
+            | Some(Commands::Contract { network, .. })
+            | Some(Commands::Test { network, .. }) => {
+                log::info!("{} nodes added from {:?}", nodes.len(), path);
+                let mut urls = vec![network.get_url()];
+                urls.extend(nodes);
+                *network = Network::Node(urls.join(","));
+                Ok(())
+            }
+            _ => Err(Error::NotSupport(
+                "--network-file with a command without --network".to_owned(),
+            )),
+        }
+    }
+
+    /// ws:// and wss:// nodes are only subscribed to by `block --follow`, everything else goes over http
+    pub(crate) fn check_transport(&self) -> Result<()> {
+        let network = match &self.command {
+            Some(Commands::Block { follow: true, .. }) => return Ok(()),
+            Some(Commands::Fund { network, .. })
+            | Some(Commands::Info { network, .. })
+            | Some(Commands::Watch { network, .. })
+            | Some(Commands::EstimateGas { network, .. })
+            | Some(Commands::Logs { network, .. })
+            | Some(Commands::Transaction { network, .. })
+            | Some(Commands::Block { network, .. })
+            | Some(Commands::Contract { network, .. })
+            | Some(Commands::Test { network, .. }) => network,
+            _ => return Ok(()),
+        };
+        match network
+            .get_url()
+            .split(',')
+            .map(str::trim)
+            .find(|url| url.starts_with("ws://") || url.starts_with("wss://"))
+        {
+            Some(url) => Err(Error::NotSupport(format!(
+                "websocket node {} without `block --follow`, use its http url",
+                url
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// whether the command draws the live dashboard
+    pub(crate) fn tui(&self) -> bool {
+        matches!(
+            self.command,
+            Some(Commands::Fund { tui: true, .. }) | Some(Commands::Test { tui: true, .. })
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn etl_cmd<P>(
+        abcid: &Option<P>,
+        tendermint: &Option<P>,
+        redis: &str,
+        redis_port: Option<u32>,
+        redis_db: Option<u8>,
+        load: bool,
+        approx_quantiles: bool,
+        store: &Store,
+        codec: Codec,
+        on_duplicate: OnDuplicate,
+        heights_file: &Option<PathBuf>,
+        min_height: Option<u64>,
+        max_height: Option<u64>,
+        parse_only: bool,
+        from_rpc: Option<String>,
+        node_timeout: Option<u64>,
+        strict_timestamps: bool,
+        verify_hashes: bool,
+        flush_interval: u64,
+        tps_window: Option<u64>,
+        max_log_line_bytes: u64,
+        gap_report: &Option<PathBuf>,
+        check_store: bool,
+        cross_check_rpc: Option<String>,
+        store_rate_limit: Option<u64>,
+        metric_fields: Option<&MetricFields>,
+    ) -> Result<()>
+    where
+        P: AsRef<Path> + std::fmt::Debug,
+    {
+        log::info!("{:?} {:?} {} {}", abcid, tendermint, redis, load);
+
+        let client = from_rpc.map(|url| TestClient::setup(Some(url), None, node_timeout));
+        if client.is_some() && (abcid.is_some() || tendermint.is_some()) {
+            return Err(Error::NotSupport(
+                "block infos are loaded either from a node or from logs, not both".to_owned(),
+            ));
+        }
+        if abcid.as_ref().map_or(false, is_stdin) && tendermint.as_ref().map_or(false, is_stdin) {
+            return Err(Error::NotSupport(
+                "only one of abcid and tendermint logs can be read from stdin".to_owned(),
+            ));
+        }
+
+        let mut heights = heights_file.as_ref().map(Heights::load).transpose()?;
+        if min_height.is_some() || max_height.is_some() {
+            let all = Heights(vec![u64::MIN..=u64::MAX]);
+            heights = Some(
+                heights
+                    .unwrap_or(all)
+                    .clamp(min_height.unwrap_or(u64::MIN), max_height.unwrap_or(u64::MAX)),
+            );
+        }
+        match heights.as_ref().map(Heights::bounds) {
+            Some(Some((min, max))) => log::info!("only heights from {} to {} are loaded", min, max),
+            Some(None) => log::warn!("no height is selected, nothing will be loaded"),
+            None => {}
+        }
+        if verify_hashes && parse_only {
+            return Err(Error::NotSupport(
+                "--verify-hashes compares with stored block infos, it cannot be used with --parse-only".to_owned(),
+            ));
+        }
+        if tps_window == Some(0) {
+            return Err(Error::NotSupport("--tps-window needs at least one second".to_owned()));
+        }
+        if let Some(path) = gap_report {
+            Gaps::format(path)?;
+        }
+        if cross_check_rpc.is_some() && client.is_some() {
+            return Err(Error::NotSupport(
+                "--cross-check-rpc checks block infos parsed from logs, it cannot be used with --from-rpc".to_owned(),
+            ));
+        }
+        if store_rate_limit == Some(0) {
+            return Err(Error::NotSupport(
+                "--store-rate-limit needs at least one write per second".to_owned(),
+            ));
+        }
+        if store_rate_limit.is_some() && parse_only {
+            return Err(Error::NotSupport(
+                "--store-rate-limit paces the writes to the store, it cannot be used with --parse-only".to_owned(),
+            ));
+        }
+        if check_store && parse_only {
+            return Err(Error::NotSupport(
+                "--check-store checks the writes of block infos, it cannot be used with --parse-only".to_owned(),
+            ));
+        }
+        let mut check = verify_hashes.then(HashCheck::default);
+        let db = if parse_only {
+            log::info!("parse only, block infos are kept in memory and not stored");
+            Rc::new(Db::memory(Some(codec)))
+        } else {
+            let mut db = open_store(store, redis, redis_port, redis_db, codec)?;
+            if let Some(ops) = store_rate_limit {
+                db.set_rate_limit(ops);
+            }
+            Rc::new(db)
+        };
+        if check_store {
+            preflight_store(&db, store)?;
+        }
+
+        if let Some(client) = client {
+            let start =
+                min_height.ok_or_else(|| Error::NotSupport("--min-height is required with --from-rpc".to_owned()))?;
+            let end = match max_height {
+                Some(end) => end,
+                None => client
+                    .block_number()
+                    .ok_or_else(|| Error::NodeUnreachable("cannot get the latest height from the node".to_owned()))?
+                    .as_u64(),
+            };
+            log::info!(
+                "loading heights {} to {} from the node, without phase timings",
+                start,
+                end
+            );
+            let (min_height, max_height) = load_rpc(&client, db.clone(), heights.as_ref(), start, end, check.as_mut())?;
+            store_bounds(&db, min_height, max_height)?;
+            let (_, gaps) = summarize(
+                &db,
+                min_height,
+                max_height,
+                approx_quantiles,
+                Some(flush_interval),
+                false,
+                heights.as_ref(),
+                tps_window,
+                metric_fields,
+            );
+            if let Some(path) = gap_report {
+                gaps.write(path)?;
+            }
+            if let Some(report) = db.throttle_report() {
+                log::info!("{}", report);
+            }
+            return check.map_or(Ok(()), |check| check.report());
+        }
+
+        let (min_height, max_height) = tendermint
+            .as_ref()
+            .map(|tendermint| {
+                parse_tendermint(
+                    tendermint,
+                    db.clone(),
+                    on_duplicate,
+                    heights.as_ref(),
+                    strict_timestamps,
+                    check.as_mut(),
+                    max_log_line_bytes as usize,
+                )
+                .with_context(|| format!("while parsing tendermint log {:?}", tendermint))
+            })
+            .transpose()?
+            .unwrap_or((u64::MAX, u64::MIN));
+        abcid
+            .as_ref()
+            .map(|abcid| {
+                parse_abcid(abcid, db.clone(), heights.as_ref(), max_log_line_bytes as usize)
+                    .with_context(|| format!("while parsing abcid log {:?}", abcid))
+            })
+            .transpose()?;
+        store_bounds(&db, min_height, max_height)?;
+        if let Some(url) = cross_check_rpc {
+            let node = TestClient::setup(Some(url), None, node_timeout);
+            cross_check_txs(&node, &db, heights.as_ref(), min_height, max_height);
+        }
+
+        let (_, gaps) = summarize(
+            &db,
+            min_height,
+            max_height,
+            approx_quantiles,
+            Some(flush_interval),
+            false,
+            heights.as_ref(),
+            tps_window,
+            metric_fields,
+        );
+        if let Some(path) = gap_report {
+            gaps.write(path)?;
+        }
+        if let Some(report) = db.throttle_report() {
+            log::info!("{}", report);
+        }
+        check.map_or(Ok(()), |check| check.report())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn summary_cmd(
+        redis: &str,
+        redis_port: Option<u32>,
+        redis_db: Option<u8>,
+        start: Option<u64>,
+        end: Option<u64>,
+        approx_quantiles: bool,
+        exclude_zero_tx: bool,
+        out: Option<Output>,
+        order: Option<(&str, bool)>,
+        limit: Option<u64>,
+        fields: Option<&MetricFields>,
+    ) -> Result<()> {
+        if let Some((name, _)) = order {
+            output::column(&BLOCK_INFO_HEADER, name)?;
+        }
+        let db = connect_db(redis, redis_port, redis_db, Codec::Json)?;
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) => (start, end),
+            _ => {
+                let (min, max) = stored_bounds(&db)?.ok_or_else(|| {
+                    Error::NotSupport("no height range is stored, pass --start and --end or run etl first".to_owned())
+                })?;
+                (start.unwrap_or(min), end.unwrap_or(max))
+            }
+        };
+        log::info!("summarizing heights {} to {}", start, end);
+        let (summaries, _) = summarize(
+            &db,
+            start,
+            end,
+            approx_quantiles,
+            None,
+            exclude_zero_tx,
+            None,
+            None,
+            None,
+        );
+        if let Some((name, reverse)) = order.filter(|_| !report::quiet()) {
+            let mut rows = (start..=end)
+                .filter_map(|h| db.fetch::<BlockInfo>(h).ok())
+                .filter(|bi| !(exclude_zero_tx && bi.txs == 0))
+                .map(|bi| bi.row())
+                .collect::<Vec<_>>();
+            output::sort_rows(&BLOCK_INFO_HEADER, &mut rows, name, reverse)?;
+            output::truncate_rows(&mut rows, limit);
+            let mut writer = BufWriter::new(std::io::stdout());
+            match fields {
+                Some(fields) => writeln!(writer, "{}", fields.header().join(","))?,
+                None => writeln!(writer, "{}", BLOCK_INFO_HEADER.join(","))?,
+            }
+            for row in &rows {
+                match fields {
+                    Some(fields) => writeln!(writer, "{}", output::line(&fields.select(row)))?,
+                    None => writeln!(writer, "{}", output::line(row))?,
+                }
+            }
+            writer.flush()?;
+        }
+        if let Some(out) = out {
+            let rows = summaries
+                .iter()
+                .map(|s| {
+                    let (min, max) = s.range();
+                    let quantiles = s.quantiles();
+                    vec![
+                        json!(s.name()),
+                        json!(s.count()),
+                        json!(s.mean()),
+                        json!(min),
+                        json!(max),
+                        json!(quantiles[0]),
+                        json!(quantiles[1]),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            out.write(&["name", "count", "mean", "min", "max", "p50", "p95"], &rows)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn profiler(network: &str, enabled: bool) -> Result<()> {
+        let url = format!("{}/configuration", network);
+        profiler::set_profiler(url.as_str(), enabled).with_context(|| format!("while setting profiler on {}", url))
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Fund Ethereum accounts
     Fund {
         /// ethereum-compatible network
         #[clap(long)]
@@ -348,11 +1927,11 @@ pub enum Commands {
         block_time: u64,
 
         /// the number of Eth Account to be fund
-        #[clap(long, default_value_t = 0)]
+        #[clap(long, parse(try_from_str = parse_count), default_value_t = 0)]
         count: u64,
 
         /// how much 0.1-eth to fund
-        #[clap(long, default_value_t = 1)]
+        #[clap(long, parse(try_from_str = parse_count), default_value_t = 1)]
         amount: u64,
 
         /// load keys from file
@@ -362,7 +1941,135 @@ pub enum Commands {
         /// re-deposit account with insufficient balance
         #[clap(long)]
         redeposit: bool,
+
+        /// override the nonce of the first transaction, subsequent ones increment from it
+        #[clap(long)]
+        start_nonce: Option<u64>,
+
+        /// derive source keys from a mnemonic instead of the key file
+        #[clap(long, env = "FETH_MNEMONIC")]
+        mnemonic: Option<String>,
+
+        /// receipt polling interval, milliseconds
+        #[clap(long, default_value_t = 1000)]
+        poll_interval: u64,
+
+        /// maximum time to wait for a receipt, seconds, three block times by default
+        #[clap(long)]
+        confirm_timeout: Option<u64>,
+
+        /// generate and fund new accounts in chunks of this size, saving each funded chunk
+        #[clap(long, parse(try_from_str = parse_count))]
+        chunk_size: Option<u64>,
+
+        /// abort funding when the chain TPS stays below this floor for --tps-window seconds
+        #[clap(long)]
+        min_tps_floor: Option<f64>,
+
+        /// how long the chain TPS may stay below --min-tps-floor, seconds
+        #[clap(long, default_value_t = 60)]
+        tps_window: u64,
+
+        /// send one transaction at a time, each waits for its receipt before the next one
+        #[clap(long)]
+        confirm_each: bool,
+
+        /// payer private key in hex or `env:VARNAME`, instead of the `.secret` file
+        #[clap(long, value_name = "KEY")]
+        source_key: Option<String>,
+
+        /// gas limit of every funding transfer
+        #[clap(long, parse(try_from_str = parse_count), default_value_t = TRANSFER_GAS)]
+        gas_limit: u64,
+
+        /// checkpoint of the confirmed funded accounts and their transactions, updated while funding,
+        /// accounts already in it are skipped
+        #[clap(long, parse(from_os_str), value_name = "CHECKPOINT")]
+        resume_fund: Option<PathBuf>,
+
+        /// index of the first account, its derivation index and its index in the key file
+        #[clap(long, default_value_t = 0)]
+        account_index_base: u64,
+
+        /// fund the accounts in a random order instead of the key file order, not with --chunk-size
+        #[clap(long)]
+        shuffle: bool,
+
+        /// seed of --shuffle, a new one is printed if none
+        #[clap(long)]
+        seed: Option<u64>,
+
+        /// show a live dashboard instead of log lines, press q to quit
+        #[clap(long)]
+        tui: bool,
+    },
+    /// Derive sequential accounts from a mnemonic and save them to a key file
+    Keys {
+        /// BIP-39 mnemonic phrase
+        #[clap(long, env = "FETH_MNEMONIC")]
+        mnemonic: String,
+
+        /// the number of accounts to derive
+        #[clap(long, parse(try_from_str = parse_count))]
+        count: u64,
+
+        /// the key file to write
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "source_keys.001")]
+        to_file: PathBuf,
+
+        /// index of the first account, its derivation index and its index in the key file
+        #[clap(long, default_value_t = 0)]
+        account_index_base: u64,
+
+        /// overwrite an existing key file
+        #[clap(long)]
+        force: bool,
+    },
+    /// Print the addresses of a key pool, one per line, without private keys
+    Addresses {
+        /// the key file: json key pairs, a json array of hex keys or one hex key per line
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "source_keys.001")]
+        source: PathBuf,
+
+        /// derive the accounts from this BIP-39 mnemonic phrase instead of reading the key file
+        #[clap(long, env = "FETH_MNEMONIC")]
+        mnemonic: Option<String>,
+
+        /// the number of accounts to derive from the mnemonic
+        #[clap(long, parse(try_from_str = parse_count), default_value_t = 1)]
+        count: u64,
+
+        /// derivation index of the first account derived from the mnemonic
+        #[clap(long, default_value_t = 0)]
+        account_index_base: u64,
+
+        /// read the accounts from an encrypted json keystore, or a directory of them, instead of the key file
+        #[clap(long, parse(from_os_str), value_name = "PATH")]
+        keystore: Option<PathBuf>,
+
+        /// password of the keystore
+        #[clap(long, env = "FETH_KEYSTORE_PASSWORD", hide_env_values = true)]
+        keystore_password: Option<String>,
+    },
+    /// Check every account of a funded key file holds at least the expected balance
+    Verify {
+        /// ethereum-compatible network
+        #[clap(long)]
+        network: Network,
+
+        /// http request timeout, seconds
+        #[clap(long)]
+        timeout: Option<u64>,
+
+        /// the key file: json key pairs, a json array of hex keys or one hex key per line
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "source_keys.001")]
+        source: PathBuf,
+
+        /// minimum balance of every account in 0.1-eth, the --amount of the fund
+        #[clap(long, parse(try_from_str = parse_count), default_value_t = 1)]
+        expected_amount: u64,
     },
+
     /// check ethereum account information
     Info {
         /// ethereum-compatible network
@@ -376,6 +2083,103 @@ pub enum Commands {
         /// ethereum address
         #[clap(long)]
         account: Address,
+
+        /// print balance in eth with this many decimal places instead of wei
+        #[clap(long)]
+        decimals: Option<usize>,
+
+        /// separate thousands with commas
+        #[clap(long)]
+        group_digits: bool,
+
+        /// write results to a file, format inferred from the extension: json, csv, txt
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        output_file: Option<PathBuf>,
+
+        /// output file format, overrides the extension
+        #[clap(long)]
+        output: Option<Format>,
+    },
+
+    /// Watch account balances and alert on depletion
+    Watch {
+        /// ethereum-compatible network
+        #[clap(long)]
+        network: Network,
+
+        /// http request timeout, seconds
+        #[clap(long)]
+        timeout: Option<u64>,
+
+        /// ethereum address to watch
+        #[clap(long)]
+        account: Option<Address>,
+
+        /// file with addresses to watch, one per line
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        accounts: Option<PathBuf>,
+
+        /// alert when balance drops below this many 0.1-eth
+        #[clap(long, parse(try_from_str = parse_count), default_value_t = 1)]
+        threshold: u64,
+
+        /// polling interval, seconds
+        #[clap(long, default_value_t = BLOCK_TIME)]
+        interval: u64,
+    },
+
+    /// Estimate gas of a sample transaction
+    EstimateGas {
+        /// ethereum-compatible network
+        #[clap(long)]
+        network: Network,
+
+        /// http request timeout, seconds
+        #[clap(long)]
+        timeout: Option<u64>,
+
+        /// recipient address
+        #[clap(long)]
+        to: Address,
+
+        /// transferred value, wei
+        #[clap(long, default_value_t = 0)]
+        value: u128,
+
+        /// hex-encoded call data
+        #[clap(long)]
+        data: Option<String>,
+
+        /// safety margin added to the estimation, percent
+        #[clap(long, default_value_t = 0)]
+        buffer_pct: u64,
+    },
+
+    /// Query logs in a block range
+    Logs {
+        /// ethereum-compatible network
+        #[clap(long)]
+        network: Network,
+
+        /// http request timeout, seconds
+        #[clap(long)]
+        timeout: Option<u64>,
+
+        /// emitting contract address
+        #[clap(long)]
+        address: Option<Address>,
+
+        /// first topic, usually the event signature hash
+        #[clap(long)]
+        topic: Option<H256>,
+
+        /// first block height or tag: latest, earliest, pending, finalized
+        #[clap(long)]
+        from: BlockTag,
+
+        /// last block height or tag, inclusive
+        #[clap(long)]
+        to: BlockTag,
     },
 
     /// Transaction Operations
@@ -391,6 +2195,10 @@ pub enum Commands {
         /// transaction hash
         #[clap(long)]
         hash: H256,
+
+        /// contract abi json decoding the function and arguments of the input, only its selector otherwise
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        abi: Option<PathBuf>,
     },
 
     /// Block Operations
@@ -403,32 +2211,286 @@ pub enum Commands {
         #[clap(long)]
         timeout: Option<u64>,
 
-        /// start block height
+        /// start block height or tag: latest, earliest, pending, finalized
         #[clap(long)]
-        start: Option<u64>,
+        start: Option<BlockTag>,
 
         /// block count, could be less than zero
-        #[clap(long)]
+        #[clap(long, parse(try_from_str = parse_signed_count), allow_hyphen_values = true)]
         count: Option<i64>,
+
+        /// keep printing new blocks, pushed by ws:// and wss:// nodes, polled otherwise, not with --output-file
+        #[clap(long)]
+        follow: bool,
+
+        /// add reverted transactions and gas used from the receipts, one request per block if the node
+        /// supports eth_getBlockReceipts
+        #[clap(long)]
+        with_receipts: bool,
+
+        /// write results to a file, format inferred from the extension: json, csv, txt
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        output_file: Option<PathBuf>,
+
+        /// output file format, overrides the extension
+        #[clap(long)]
+        output: Option<Format>,
+
+        /// split --output-file, a new file every N blocks or every Nmin minutes of block time,
+        /// named after the starting height or time
+        #[clap(long, value_name = "N|Nmin")]
+        metric_rotate: Option<Rotate>,
+
+        /// skip blocks without transactions, the skipped count is still reported
+        #[clap(long)]
+        only_heights_with_txs: bool,
+
+        /// print and write the blocks ordered by this column, e.g. txs or block_time, instead of by height
+        #[clap(long, value_name = "FIELD")]
+        sort_by: Option<String>,
+
+        /// reverse the order of the blocks, the busiest or slowest first with --sort-by
+        #[clap(long)]
+        reverse: bool,
+
+        /// print and write only the first N blocks, after sorting
+        #[clap(long, parse(try_from_str = parse_count), value_name = "N")]
+        limit: Option<u64>,
+    },
+
+    /// Observed TPS of the chain over its last blocks, with a per-block breakdown
+    ChainTps {
+        /// ethereum-compatible network
+        #[clap(long)]
+        network: Network,
+
+        /// http request timeout, seconds
+        #[clap(long)]
+        timeout: Option<u64>,
+
+        /// number of latest blocks
+        #[clap(long, parse(try_from_str = parse_count), default_value_t = 100)]
+        blocks: u64,
+    },
+
+    /// Run the test once per combination of parameter values and compare the results
+    Matrix {
+        /// json config: {"args": [test arguments of every run], "params": {"option": [values]}}
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        config: PathBuf,
+
+        /// directory of the run summaries, run-NNN.json
+        #[clap(long, parse(from_os_str), value_name = "DIR", default_value = "matrix")]
+        out_dir: PathBuf,
+
+        /// write the matrix to a file, format inferred from the extension: json, csv, txt
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        output_file: Option<PathBuf>,
+
+        /// output file format, overrides the extension
+        #[clap(long)]
+        output: Option<Format>,
+    },
+
+    /// Replace a stuck transaction of an account with a zero-value self-transfer at its nonce
+    Cancel {
+        /// ethereum-compatible network
+        #[clap(long)]
+        network: Network,
+
+        /// http request timeout, seconds
+        #[clap(long)]
+        timeout: Option<u64>,
+
+        /// private key of the account in hex or `env:VARNAME`
+        #[clap(long, value_name = "KEY")]
+        account_key: String,
+
+        /// nonce to cancel, the first one not mined yet by default
+        #[clap(long)]
+        nonce: Option<u64>,
+
+        /// gas price of the stuck transaction in wei, the node gas price by default,
+        /// the cancellation pays 12.5% more to replace it
+        #[clap(long)]
+        gas_price: Option<u64>,
+
+        /// maximum time to wait for the cancellation to be mined, seconds, three block times by default
+        #[clap(long)]
+        confirm_timeout: Option<u64>,
+    },
+
+    /// Decode a raw signed transaction
+    Decode {
+        /// hex encoded signed transaction
+        #[clap(long)]
+        raw: String,
     },
 
     /// ETL procession
     Etl {
-        /// abcid log file
+        /// abcid log file, `-` for stdin
         #[clap(long)]
         abcid: Option<String>,
 
-        /// tendermint log file
+        /// tendermint log file, `-` for stdin
         #[clap(long)]
         tendermint: Option<String>,
 
-        /// redis db address
+        /// redis address, host[:port][/db], the host may be an ipv6 literal like [::1],
+        /// or unix:<socket path> with the db from --redis-db
         #[clap(long, default_value = "127.0.0.1")]
         redis: String,
 
+        /// redis port, overrides the one in `--redis`, 6379 by default
+        #[clap(long)]
+        redis_port: Option<u32>,
+
+        /// redis db index, overrides the one in `--redis`, 0 by default
+        #[clap(long)]
+        redis_db: Option<u8>,
+
         /// load data
         #[clap(long)]
         load: bool,
+
+        /// estimate summary quantiles in bounded memory instead of buffering all samples
+        #[clap(long)]
+        approx_quantiles: bool,
+
+        /// block info storage: redis, file:<path> (JSONL, json codec only)
+        #[clap(long, default_value = "redis")]
+        store: Store,
+
+        /// block info storage codec: json, bincode
+        #[clap(long, default_value = "json")]
+        store_codec: Codec,
+
+        /// heights executed more than once: first, last, warn (keeps the last)
+        #[clap(long, default_value = "warn")]
+        on_duplicate: OnDuplicate,
+
+        /// only load the heights listed in this file, heights or `start-end` ranges
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        heights_file: Option<PathBuf>,
+
+        /// ignore heights below this one, whatever the logs contain
+        #[clap(long)]
+        min_height: Option<u64>,
+
+        /// ignore heights above this one, whatever the logs contain
+        #[clap(long)]
+        max_height: Option<u64>,
+
+        /// validate the logs and print the summary without storing anything
+        #[clap(long)]
+        parse_only: bool,
+
+        /// load block infos from the blocks of this node instead of logs, from --min-height to
+        /// --max-height or the latest height, phase timings are left empty
+        #[clap(long, value_name = "NETWORK")]
+        from_rpc: Option<Network>,
+
+        /// abort on a tendermint log line with an unparseable timestamp instead of skipping it
+        #[clap(long)]
+        strict_timestamps: bool,
+
+        /// compare the app hashes of the tendermint log, or the block hashes of --from-rpc, with the
+        /// ones a previous run stored for the same heights, and fail on a mismatch
+        #[clap(long)]
+        verify_hashes: bool,
+
+        /// rows printed to stdout between two flushes, lower shows rows sooner, higher prints faster
+        #[clap(long, parse(try_from_str = parse_count), default_value_t = 1000)]
+        flush_interval: u64,
+
+        /// add a column to the rows, the TPS over the trailing N seconds of block time
+        #[clap(long, value_name = "SECONDS")]
+        tps_window: Option<u64>,
+
+        /// skip log lines longer than this many bytes instead of buffering them
+        #[clap(long, parse(try_from_str = parse_count), default_value_t = 1 << 20)]
+        max_log_line_bytes: u64,
+
+        /// write the missing height ranges, their total and the largest gap to this .csv or .json file
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        gap_report: Option<PathBuf>,
+
+        /// before loading, write and read back a sentinel key in redis, report the round trip and
+        /// warn of an eviction policy which may drop block infos, abort if the sentinel is lost
+        #[clap(long)]
+        check_store: bool,
+
+        /// compare the transaction count of every block parsed from the logs with the one of the
+        /// same block on this node, and report the mismatched heights
+        #[clap(long, value_name = "NETWORK")]
+        cross_check_rpc: Option<Network>,
+
+        /// write at most this many block infos per second, to spare a redis shared with other workloads
+        #[clap(long, parse(try_from_str = parse_count), value_name = "OPS")]
+        store_rate_limit: Option<u64>,
+
+        /// comma separated columns of the printed block rows, e.g. height,block_time, out of
+        /// height, timestamp, txs, valid_txs, block_time and the phases begin, snapshot, end, commit, commit_evm
+        #[clap(long, value_name = "FIELDS")]
+        metric_fields: Option<MetricFields>,
+    },
+
+    /// Summarize block infos already stored by a previous ETL run
+    Summary {
+        /// redis address, host[:port][/db], the host may be an ipv6 literal like [::1],
+        /// or unix:<socket path> with the db from --redis-db
+        #[clap(long, default_value = "127.0.0.1")]
+        redis: String,
+
+        /// redis port, overrides the one in `--redis`, 6379 by default
+        #[clap(long)]
+        redis_port: Option<u32>,
+
+        /// redis db index, overrides the one in `--redis`, 0 by default
+        #[clap(long)]
+        redis_db: Option<u8>,
+
+        /// first block height, the lowest one stored by etl by default
+        #[clap(long)]
+        start: Option<u64>,
+
+        /// last block height, inclusive, the highest one stored by etl by default
+        #[clap(long)]
+        end: Option<u64>,
+
+        /// estimate summary quantiles in bounded memory instead of buffering all samples
+        #[clap(long)]
+        approx_quantiles: bool,
+
+        /// leave blocks without transactions out of the statistics
+        #[clap(long)]
+        exclude_zero_tx: bool,
+
+        /// also print the stored blocks, ordered by this column, e.g. txs, block_time or commit
+        #[clap(long, value_name = "FIELD")]
+        sort_by: Option<String>,
+
+        /// reverse the order of the printed blocks, the busiest or slowest first with --sort-by
+        #[clap(long)]
+        reverse: bool,
+
+        /// print only the first N blocks, after sorting, the statistics still cover the whole range
+        #[clap(long, parse(try_from_str = parse_count), value_name = "N")]
+        limit: Option<u64>,
+
+        /// comma separated columns of the printed blocks, e.g. height,block_time, out of height,
+        /// timestamp, txs, valid_txs, block_time and the phases begin, snapshot, end, commit, commit_evm
+        #[clap(long, value_name = "FIELDS")]
+        metric_fields: Option<MetricFields>,
+
+        /// write results to a file, format inferred from the extension: json, csv, txt
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        output_file: Option<PathBuf>,
+
+        /// output file format, overrides the extension
+        #[clap(long)]
+        output: Option<Format>,
     },
 
     /// Profiler operations
@@ -465,23 +2527,29 @@ pub enum Commands {
         #[clap(long)]
         network: Network,
 
-        /// Test mode: basic transfer transaction, contract call transaction
+        /// Test mode: basic transfer transaction, contract call transaction, self zero-value transfer
         #[clap(long)]
         mode: TestMode,
 
+        /// recipients of the transfers: self (only gas is spent), pool (the value moves between the
+        /// source accounts, round-robin), fixed:<address> (all the value goes to one address) or random
+        /// (the value leaves the pool to new addresses), self with the self mode and random otherwise
+        #[clap(long, value_name = "PATTERN")]
+        recipient_pattern: Option<RecipientPattern>,
+
         /// Delay time for next batch of transactions
         #[clap(long, default_value_t = 15)]
         delay: u64,
 
         /// The max thread pool size
-        #[clap(long, default_value_t = 200)]
+        #[clap(long, parse(try_from_str = parse_count), default_value_t = 200)]
         max_threads: u64,
 
-        /// The count of transactions sent by a source key
-        #[clap(long, default_value_t = 0)]
+        /// The count of transactions sent by a source key, the total is the pool size times this
+        #[clap(long, visible_alias = "tx-per-account", parse(try_from_str = parse_count), default_value_t = 0)]
         count: u64,
 
-        /// the source account file
+        /// the source account file: json key pairs, a json array of hex keys or one hex key per line
         #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "source_keys.001")]
         source: PathBuf,
 
@@ -500,5 +2568,218 @@ pub enum Commands {
         /// If need to check balance of source keys
         #[clap(long)]
         check_balance: bool,
+
+        /// count a transaction as succeeded only if its receipt status is 1
+        #[clap(long)]
+        require_receipt_status: bool,
+
+        /// receipt polling interval, milliseconds
+        #[clap(long, default_value_t = 1000)]
+        poll_interval: u64,
+
+        /// maximum time to wait for a receipt, seconds, three block times by default
+        #[clap(long)]
+        confirm_timeout: Option<u64>,
+
+        /// transaction type: legacy, 2930, 1559
+        #[clap(long, default_value = "legacy")]
+        tx_type: TxType,
+
+        /// json access list file for 2930 transactions, only with --tx-type 2930
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        access_list: Option<PathBuf>,
+
+        /// adjust concurrency every round to keep latency below --target-latency (AIMD)
+        #[clap(long)]
+        adaptive: bool,
+
+        /// adaptive mode latency target of the round p95, milliseconds
+        #[clap(long, default_value_t = 1000)]
+        target_latency: u64,
+
+        /// adaptive mode concurrency increase per round, also the initial concurrency
+        #[clap(long, default_value_t = 4)]
+        adaptive_step: u64,
+
+        /// only sign `count` transactions per source key and report the signing throughput
+        #[clap(long)]
+        sign_bench: bool,
+
+        /// write a json summary of the run to this file, also when the run is interrupted or fails
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        summary_json: Option<PathBuf>,
+
+        /// also rewrite --summary-json every this many seconds during the run, marked not completed
+        #[clap(long, value_name = "SECONDS")]
+        metric_flush_interval: Option<u64>,
+
+        /// exit with an error unless the final metrics satisfy this, e.g. tps>=500, all must hold
+        #[clap(long = "assert", value_name = "EXPR")]
+        asserts: Vec<Assertion>,
+
+        /// pause before a round while the node has more pending transactions than this
+        #[clap(long, parse(try_from_str = parse_count))]
+        backpressure_threshold: Option<u64>,
+
+        /// gas limit of every test transaction, 21000 by default, estimated with --data-template
+        #[clap(long, parse(try_from_str = parse_count))]
+        gas_limit: Option<u64>,
+
+        /// hex calldata of every test transaction with placeholders expanded per transaction:
+        /// {recipient}, {sender}, {index}, {random:uint<N>}, {random:address}
+        #[clap(long, value_name = "TEMPLATE")]
+        data_template: Option<DataTemplate>,
+
+        /// contract called by every test transaction with the --data-template calldata and no value,
+        /// also the target of the gas estimation, instead of the --recipient-pattern recipients
+        #[clap(long, value_name = "ADDRESS")]
+        to: Option<Address>,
+
+        /// seed of the random placeholders of --data-template and of --shuffle, a new one is printed if none
+        #[clap(long)]
+        seed: Option<u64>,
+
+        /// send from the source accounts in a random order instead of the key file order, the
+        /// transactions of every account keep their nonce order
+        #[clap(long)]
+        shuffle: bool,
+
+        /// exclude source accounts whose balance cannot pay the run or whose nonce is unknown
+        #[clap(long)]
+        precheck: bool,
+
+        /// wait at most this many seconds for the receipt of every transaction, then count it as failed
+        #[clap(long, value_name = "SECONDS")]
+        tx_deadline: Option<u64>,
+
+        /// what to do with a transaction past --tx-deadline: fail, bump (resend with a higher gas price)
+        /// or cancel (zero-value self-transfer at the same nonce)
+        #[clap(long, default_value = "fail")]
+        on_deadline: OnDeadline,
+
+        /// seconds without any completed transaction, while some remain, before the run counts as
+        /// stalled and the in-flight count, last error, node health and pending nonces are printed
+        #[clap(long, value_name = "SECONDS")]
+        stall_window: Option<u64>,
+
+        /// what to do with a stalled run: abort or continue, waits for blocks and backpressure pauses
+        /// do not count as stalled
+        #[clap(long, default_value = "abort")]
+        on_stall: OnStall,
+
+        /// stop sending once the gas paid by the mined transactions, from their receipts, plus the most
+        /// the in-flight ones may pay would exceed this many wei, needs --require-receipt-status or --tx-deadline
+        #[clap(long, value_name = "WEI")]
+        max_gas_spend: Option<u128>,
+
+        /// send exactly the transactions of this JSONL file instead of --count rounds, one
+        /// {"from_index", "to", "value", "data", "gas"} object per line, from_index is a source key index
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        transactions_from_file: Option<PathBuf>,
+
+        /// top up the source accounts from the root key right before the first round when their
+        /// balance cannot pay the run, fundings are counted apart from the load and left out of the TPS
+        #[clap(long)]
+        jit_fund: bool,
+
+        /// pick the node of a --network list of every source account by hashing the account, so it keeps
+        /// its node whatever the list order, instead of round-robin over the list in key file order
+        #[clap(long)]
+        pin_node_per_account: bool,
+
+        /// refuse to run when the chain id in the source key file header differs from the node's
+        #[clap(long)]
+        validate_source: bool,
+
+        /// only warn on a --validate-source mismatch
+        #[clap(long)]
+        force: bool,
+
+        /// a previous --summary-json to compare TPS and latencies against
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        baseline: Option<PathBuf>,
+
+        /// exit with an error if TPS or a latency is worse than the baseline by more than this percentage
+        #[clap(long, default_value_t = 10.0)]
+        regression_pct: f64,
+
+        /// show a live dashboard instead of log lines, press q to quit
+        #[clap(long)]
+        tui: bool,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(host: &str, port: u32, db: u8) -> RedisAddr {
+        RedisAddr {
+            host: host.to_owned(),
+            port,
+            db,
+        }
+    }
+
+    #[test]
+    fn count_parse() {
+        assert_eq!(parse_count("1_000"), Ok(1000));
+        assert_eq!(parse_count("10k"), Ok(10_000));
+        assert_eq!(parse_count("10K"), Ok(10_000));
+        assert_eq!(parse_count("1.5m"), Ok(1_500_000));
+        assert_eq!(parse_count("2g"), Ok(2_000_000_000));
+        assert_eq!(parse_count("18446744073709551615"), Ok(u64::MAX));
+        assert!(parse_count("18446744073709551616").is_err());
+        assert!(parse_count("18446744073709552k").is_err());
+        assert!(parse_count("-1").is_err());
+        assert!(parse_count("1.5").is_err());
+        assert!(parse_count("1.0005k").is_err());
+        assert!(parse_count("1.").is_err());
+        assert!(parse_count("_1").is_err());
+        assert!(parse_count("1__0").is_err());
+        assert!(parse_count("10t").is_err());
+        assert!(parse_count("").is_err());
+    }
+
+    #[test]
+    fn signed_count_parse() {
+        assert_eq!(parse_signed_count("10"), Ok(10));
+        assert_eq!(parse_signed_count("-10k"), Ok(-10_000));
+        assert_eq!(parse_signed_count("-1_000"), Ok(-1000));
+        assert!(parse_signed_count("--1").is_err());
+        assert!(parse_signed_count("10g0").is_err());
+        assert!(parse_signed_count("18446744073709551615").is_err());
+    }
+
+    #[test]
+    fn block_txs_limit() {
+        assert_eq!(block_txs(3368, 666), Some(4034));
+        assert_eq!(block_txs(MAX_BLOCK_TXS, 0), Some(MAX_BLOCK_TXS));
+        assert_eq!(block_txs(MAX_BLOCK_TXS, 1), None);
+        assert_eq!(block_txs(u64::MAX, 1), None);
+    }
+
+    #[test]
+    fn redis_addr_parse() {
+        assert_eq!("[::1]:6380".parse(), Ok(addr("[::1]", 6380, 0)));
+        assert_eq!("[::1]".parse(), Ok(addr("[::1]", 6379, 0)));
+        assert_eq!("::1".parse(), Ok(addr("[::1]", 6379, 0)));
+        assert_eq!("localhost/3".parse(), Ok(addr("localhost", 6379, 3)));
+        assert_eq!("redis://10.0.0.1:7000/15".parse(), Ok(addr("10.0.0.1", 7000, 15)));
+    }
+
+    #[test]
+    fn redis_addr_invalid() {
+        for s in [
+            "[]",
+            "[]:6379",
+            "localhost:port",
+            "localhost:65536",
+            "[::1]:x",
+            "localhost/256",
+            "/1",
+        ] {
+            assert!(s.parse::<RedisAddr>().is_err(), "{} should be rejected", s);
+        }
+    }
+}
@@ -1,19 +1,181 @@
 use derive_more::Display;
 use feth::error::{Error, Result};
 use redis::Client;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// leading byte of a bincode record, json records always start with '{'
+const BINCODE_MAGIC: u8 = 0xb2;
+
+/// second byte of a bincode record, bumped whenever `BlockInfo` changes shape
+const BINCODE_SCHEMA: u8 = 1;
+
+/// leading byte of the bincode records written before the schema byte
+const LEGACY_BINCODE_MAGIC: u8 = 0xb1;
+
+/// key written and deleted by [`Db::check_write`], block infos use height keys
+const SENTINEL_KEY: &str = "feth:sentinel";
+/// the sentinel expires by itself if the check dies before deleting it
+const SENTINEL_TTL_MS: u64 = 60_000;
+
+/// prefix of the redis keys written by [`Db::set_meta`]
+const META_KEY: &str = "feth:meta";
 
 #[derive(Debug, Display)]
-#[display(fmt = "{}, {}, {:?}", proto, endpoint, client)]
+#[display(fmt = "{}, {}, {}", proto, codec, endpoint)]
 pub struct Db {
     endpoint: String,
     proto: Proto,
-    client: Client,
+    codec: Codec,
+    backend: Backend,
+    throttle: RefCell<Option<Throttle>>,
+}
+
+/// Paces the writes of a store, shared by every writer of the process as they go through one `Db`
+#[derive(Debug)]
+struct Throttle {
+    ops_per_sec: u64,
+    interval: Duration,
+    next: Instant,
+    writes: u64,
+    waits: u64,
+    waited: Duration,
+}
+
+impl Throttle {
+    fn new(ops_per_sec: u64) -> Self {
+        Self {
+            ops_per_sec,
+            interval: Duration::from_nanos(1_000_000_000 / ops_per_sec),
+            next: Instant::now(),
+            writes: 0,
+            waits: 0,
+            waited: Duration::ZERO,
+        }
+    }
+
+    /// sleep until the next write is allowed
+    fn wait(&mut self) {
+        let now = Instant::now();
+        if self.next > now {
+            let wait = self.next - now;
+            std::thread::sleep(wait);
+            self.waits += 1;
+            self.waited += wait;
+        }
+        self.next = self.next.max(now) + self.interval;
+        self.writes += 1;
+    }
+}
+
+#[derive(Debug)]
+enum Backend {
+    Redis(Client),
+    File(RefCell<FileStore>),
+    Memory(RefCell<HashMap<u64, Vec<u8>>>, RefCell<HashMap<String, String>>),
+}
+
+/// JSONL file store, one `{"key":..,"value":..}` record per line, or `{"meta":..,"value":..}` for metadata,
+/// the last record of a key wins
+#[derive(Debug)]
+struct FileStore {
+    file: File,
+    records: HashMap<u64, Vec<u8>>,
+    meta: HashMap<String, String>,
+}
+
+/// Where ETL stores block infos
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Store {
+    Redis,
+    File(PathBuf),
+}
+
+impl std::str::FromStr for Store {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "redis" => Ok(Self::Redis),
+            Some(("file", path)) if !path.is_empty() => Ok(Self::File(PathBuf::from(path))),
+            _ => Err("Invalid store: redis and file:<path> are supported".to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Json,
+    Bincode,
+}
+
+impl std::fmt::Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let codec = match self {
+            Self::Json => "json",
+            Self::Bincode => "bincode",
+        };
+        write!(f, "{}", codec)
+    }
+}
+
+impl std::str::FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "bincode" => Ok(Self::Bincode),
+            _ => Err("Invalid codec: json and bincode are supported".to_owned()),
+        }
+    }
+}
+
+impl Codec {
+    /// serialize a value, bincode records are prefixed with a magic byte and the schema version
+    pub fn encode<T: Serialize>(&self, data: &T) -> Result<Vec<u8>> {
+        match self {
+            Self::Json => serde_json::to_vec(data).map_err(|e| Error::Codec(e.to_string())),
+            Self::Bincode => {
+                let mut raw = vec![BINCODE_MAGIC, BINCODE_SCHEMA];
+                bincode::serialize_into(&mut raw, data).map_err(|e| Error::Codec(e.to_string()))?;
+                Ok(raw)
+            }
+        }
+    }
+
+    /// deserialize a value, the codec is detected from the record itself
+    pub fn decode<T: DeserializeOwned>(raw: &[u8]) -> Result<T> {
+        match raw {
+            [BINCODE_MAGIC, BINCODE_SCHEMA, data @ ..] => {
+                bincode::deserialize(data).map_err(|e| Error::Codec(e.to_string()))
+            }
+            [BINCODE_MAGIC, schema, ..] => Err(Error::Codec(format!(
+                "bincode record of schema {}, this build reads schema {}, re-run etl",
+                schema, BINCODE_SCHEMA
+            ))),
+            [LEGACY_BINCODE_MAGIC, ..] => Err(Error::Codec(
+                "bincode record without a schema version, re-run etl".to_owned(),
+            )),
+            _ => serde_json::from_slice(raw).map_err(|e| Error::Codec(e.to_string())),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Proto {
     Url,
     Unix,
+    File,
+    Memory,
 }
 
 impl std::fmt::Display for Proto {
@@ -21,6 +183,8 @@ impl std::fmt::Display for Proto {
         let proto = match self {
             Self::Unix => "unix socket",
             Self::Url => "redis",
+            Self::File => "file",
+            Self::Memory => "memory",
         };
         write!(f, "{}", proto)
     }
@@ -34,6 +198,7 @@ impl Db {
         path: &str,
         port: Option<u32>,
         db: Option<u8>,
+        codec: Option<Codec>,
     ) -> Result<Self> {
         let proto = proto.unwrap_or(Proto::Url);
         let endpoint = match proto {
@@ -51,26 +216,264 @@ impl Db {
                 }
                 endpoint
             }
-            Proto::Unix => return Err(Error::NotSupport("Unix socket is not supported currently".to_string())),
+            Proto::Unix => {
+                if auth.is_some() {
+                    return Err(Error::NotSupport("auth over a unix socket is not supported".to_owned()));
+                }
+                let mut endpoint = format!("redis+unix://{}", path);
+                if let Some(db) = db {
+                    endpoint.push_str(format!("?db={}", db).as_str());
+                }
+                endpoint
+            }
+            Proto::File | Proto::Memory => {
+                return Err(Error::NotSupport(format!("{} is not supported currently", proto)))
+            }
         };
 
         Ok(Self {
             proto,
-            client: Client::open(endpoint.as_str())?,
+            codec: codec.unwrap_or(Codec::Json),
+            backend: Backend::Redis(Client::open(endpoint.as_str())?),
             endpoint,
+            throttle: RefCell::new(None),
+        })
+    }
+
+    /// open a JSONL file store, existing records are loaded so later runs can read them back
+    pub fn open_file(path: &Path, codec: Option<Codec>) -> Result<Self> {
+        let codec = codec.unwrap_or(Codec::Json);
+        if codec != Codec::Json {
+            return Err(Error::NotSupport(format!(
+                "{} codec is not supported by the file store",
+                codec
+            )));
+        }
+        let (mut records, mut meta) = (HashMap::new(), HashMap::new());
+        if path.exists() {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let record: Value = serde_json::from_str(line?.as_str()).map_err(|e| Error::Codec(e.to_string()))?;
+                match (record["key"].as_u64(), record["meta"].as_str(), record.get("value")) {
+                    (Some(key), None, Some(value)) => {
+                        records.insert(key, serde_json::to_vec(value).map_err(|e| Error::Codec(e.to_string()))?);
+                    }
+                    (None, Some(name), Some(Value::String(value))) => {
+                        meta.insert(name.to_owned(), value.clone());
+                    }
+                    _ => return Err(Error::Codec(format!("invalid record {} in {:?}", record, path))),
+                }
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            proto: Proto::File,
+            codec,
+            backend: Backend::File(RefCell::new(FileStore { file, records, meta })),
+            endpoint: path.display().to_string(),
+            throttle: RefCell::new(None),
+        })
+    }
+
+    /// a store living only as long as the process, nothing is persisted
+    pub fn memory(codec: Option<Codec>) -> Self {
+        Self {
+            proto: Proto::Memory,
+            codec: codec.unwrap_or(Codec::Json),
+            backend: Backend::Memory(RefCell::new(HashMap::new()), RefCell::new(HashMap::new())),
+            endpoint: String::new(),
+            throttle: RefCell::new(None),
+        }
+    }
+
+    /// check the store is reachable
+    pub fn ping(&self) -> Result<()> {
+        match &self.backend {
+            Backend::Redis(client) => {
+                let mut conn = client.get_connection()?;
+                Ok(redis::cmd("PING").query(&mut conn)?)
+            }
+            Backend::File(_) | Backend::Memory(..) => Ok(()),
+        }
+    }
+
+    /// number of databases configured on the redis server, `None` if `CONFIG` is not allowed
+    pub fn databases(&self) -> Result<Option<u64>> {
+        match &self.backend {
+            Backend::Redis(client) => {
+                let mut conn = client.get_connection()?;
+                let config: Vec<String> = match redis::cmd("CONFIG").arg("GET").arg("databases").query(&mut conn) {
+                    Ok(config) => config,
+                    Err(e) => {
+                        log::debug!("failed to get redis databases: {}", e);
+                        return Ok(None);
+                    }
+                };
+                Ok(config.get(1).and_then(|n| n.parse().ok()))
+            }
+            Backend::File(_) | Backend::Memory(..) => Ok(None),
+        }
+    }
+
+    /// `maxmemory-policy` of the redis server, `None` if `CONFIG` is not allowed
+    pub fn eviction_policy(&self) -> Result<Option<String>> {
+        match &self.backend {
+            Backend::Redis(client) => {
+                let mut conn = client.get_connection()?;
+                let config: Vec<String> = match redis::cmd("CONFIG").arg("GET").arg("maxmemory-policy").query(&mut conn)
+                {
+                    Ok(config) => config,
+                    Err(e) => {
+                        log::debug!("failed to get redis maxmemory-policy: {}", e);
+                        return Ok(None);
+                    }
+                };
+                Ok(config.get(1).cloned())
+            }
+            Backend::File(_) | Backend::Memory(..) => Ok(None),
+        }
+    }
+
+    /// write, read back and delete a sentinel key, returns the round trip time,
+    /// file and memory stores are checked when opened
+    pub fn check_write(&self) -> Result<Duration> {
+        match &self.backend {
+            Backend::Redis(client) => {
+                let begin = Instant::now();
+                let mut conn = client.get_connection()?;
+                let sentinel = format!("{}:{}", SENTINEL_KEY, std::process::id());
+                let value = chrono::Utc::now().to_rfc3339();
+                redis::cmd("SET")
+                    .arg(&sentinel)
+                    .arg(&value)
+                    .arg("PX")
+                    .arg(SENTINEL_TTL_MS)
+                    .query::<()>(&mut conn)?;
+                let read: Option<String> = redis::cmd("GET").arg(&sentinel).query(&mut conn)?;
+                redis::cmd("DEL").arg(&sentinel).query::<()>(&mut conn)?;
+                match read {
+                    Some(read) if read == value => Ok(begin.elapsed()),
+                    read => Err(Error::Unavailable(format!(
+                        "sentinel {} written to {} but read back as {:?}",
+                        sentinel, self.endpoint, read
+                    ))),
+                }
+            }
+            Backend::File(_) | Backend::Memory(..) => Ok(Duration::ZERO),
+        }
+    }
+
+    /// pace the inserts to at most `ops_per_sec`, 0 removes the limit
+    pub fn set_rate_limit(&mut self, ops_per_sec: u64) {
+        *self.throttle.get_mut() = (ops_per_sec > 0).then(|| Throttle::new(ops_per_sec));
+    }
+
+    /// how much the rate limit held back the inserts, `None` without a limit
+    pub fn throttle_report(&self) -> Option<String> {
+        let throttle = self.throttle.borrow();
+        let t = throttle.as_ref()?;
+        Some(match t.waits {
+            0 => format!(
+                "store rate limit of {} ops/s never reached, {} writes",
+                t.ops_per_sec, t.writes
+            ),
+            waits => format!(
+                "store rate limit of {} ops/s held back {} of {} writes, {:.1}s in total",
+                t.ops_per_sec,
+                waits,
+                t.writes,
+                t.waited.as_secs_f64()
+            ),
         })
     }
 
     /// insert a data
     pub fn insert(&self, key: u64, data: &[u8]) -> Result<()> {
-        let mut conn = self.client.get_connection()?;
-        Ok(redis::cmd("SET").arg(key).arg(data).query(&mut conn)?)
+        if let Some(throttle) = self.throttle.borrow_mut().as_mut() {
+            throttle.wait();
+        }
+        match &self.backend {
+            Backend::Redis(client) => {
+                let mut conn = client.get_connection()?;
+                Ok(redis::cmd("SET").arg(key).arg(data).query(&mut conn)?)
+            }
+            Backend::File(store) => {
+                let mut store = store.borrow_mut();
+                let value: Value = serde_json::from_slice(data).map_err(|e| Error::Codec(e.to_string()))?;
+                writeln!(store.file, "{}", json!({"key": key, "value": value}))?;
+                store.records.insert(key, data.to_vec());
+                Ok(())
+            }
+            Backend::Memory(records, _) => {
+                records.borrow_mut().insert(key, data.to_vec());
+                Ok(())
+            }
+        }
     }
 
     /// get a data
-    pub fn get(&self, key: u64) -> Result<String> {
-        let mut conn = self.client.get_connection()?;
-        let res: String = redis::cmd("GET").arg(key).query(&mut conn)?;
-        Ok(res)
+    pub fn get(&self, key: u64) -> Result<Vec<u8>> {
+        let res: Option<Vec<u8>> = match &self.backend {
+            Backend::Redis(client) => {
+                let mut conn = client.get_connection()?;
+                redis::cmd("GET").arg(key).query(&mut conn)?
+            }
+            Backend::File(store) => store.borrow().records.get(&key).cloned(),
+            Backend::Memory(records, _) => records.borrow().get(&key).cloned(),
+        };
+        res.ok_or_else(|| Error::Unknown(format!("key {} not found", key)))
+    }
+
+    /// store a named value about the stored data, kept apart from the block infos
+    pub fn set_meta(&self, name: &str, value: &str) -> Result<()> {
+        match &self.backend {
+            Backend::Redis(client) => {
+                let mut conn = client.get_connection()?;
+                Ok(redis::cmd("SET")
+                    .arg(format!("{}:{}", META_KEY, name))
+                    .arg(value)
+                    .query(&mut conn)?)
+            }
+            Backend::File(store) => {
+                let mut store = store.borrow_mut();
+                writeln!(store.file, "{}", json!({"meta": name, "value": value}))?;
+                store.meta.insert(name.to_owned(), value.to_owned());
+                Ok(())
+            }
+            Backend::Memory(_, meta) => {
+                meta.borrow_mut().insert(name.to_owned(), value.to_owned());
+                Ok(())
+            }
+        }
+    }
+
+    /// get a value stored by [`Db::set_meta`], `None` if it was never set
+    pub fn get_meta(&self, name: &str) -> Result<Option<String>> {
+        match &self.backend {
+            Backend::Redis(client) => {
+                let mut conn = client.get_connection()?;
+                Ok(redis::cmd("GET")
+                    .arg(format!("{}:{}", META_KEY, name))
+                    .query(&mut conn)?)
+            }
+            Backend::File(store) => Ok(store.borrow().meta.get(name).cloned()),
+            Backend::Memory(_, meta) => Ok(meta.borrow().get(name).cloned()),
+        }
+    }
+
+    /// encode and insert a value, returns the size of the stored record
+    pub fn put<T: Serialize>(&self, key: u64, data: &T) -> Result<usize> {
+        let raw = self.codec.encode(data)?;
+        self.insert(key, &raw)?;
+        Ok(raw.len())
+    }
+
+    /// get and decode a value
+    pub fn fetch<T: DeserializeOwned>(&self, key: u64) -> Result<T> {
+        Codec::decode(&self.get(key)?)
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
     }
 }
@@ -0,0 +1,332 @@
+//! Decode raw signed transactions, legacy and EIP-2718 typed envelopes, and their call input
+
+use crate::error::{Error, Result};
+use libsecp256k1::{recover, Message, RecoveryId, Signature};
+use rlp::{Rlp, RlpStream};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use web3::{
+    ethabi::Contract,
+    types::{Address, Bytes, H160, H256, U256},
+};
+
+#[derive(Debug, Clone)]
+pub struct DecodedTx {
+    /// 0 for legacy transactions
+    pub tx_type: u8,
+    pub chain_id: Option<u64>,
+    pub nonce: U256,
+    /// `to` is empty for contract creations
+    pub to: Option<Address>,
+    pub value: U256,
+    pub gas: U256,
+    pub gas_price: Option<U256>,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub data: Bytes,
+    pub v: u64,
+    pub r: H256,
+    pub s: H256,
+    pub hash: H256,
+    pub sender: Address,
+}
+
+impl DecodedTx {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "type": self.tx_type,
+            "hash": self.hash,
+            "chainId": self.chain_id,
+            "nonce": self.nonce,
+            "from": self.sender,
+            "to": self.to,
+            "value": self.value,
+            "gas": self.gas,
+            "gasPrice": self.gas_price,
+            "maxFeePerGas": self.max_fee_per_gas,
+            "maxPriorityFeePerGas": self.max_priority_fee_per_gas,
+            "data": self.data,
+            "v": self.v,
+            "r": self.r,
+            "s": self.s,
+        })
+    }
+}
+
+fn rlp_err(e: rlp::DecoderError) -> Error {
+    Error::Codec(format!("invalid rlp: {}", e))
+}
+
+fn u256_at(rlp: &Rlp, idx: usize) -> Result<U256> {
+    let data = rlp.at(idx).and_then(|item| item.data()).map_err(rlp_err)?;
+    if data.len() > 32 {
+        return Err(Error::Codec(format!("field {} is longer than 32 bytes", idx)));
+    }
+    Ok(U256::from_big_endian(data))
+}
+
+fn to_at(rlp: &Rlp, idx: usize) -> Result<Option<Address>> {
+    let data = rlp.at(idx).and_then(|item| item.data()).map_err(rlp_err)?;
+    match data.len() {
+        0 => Ok(None),
+        20 => Ok(Some(H160::from_slice(data))),
+        n => Err(Error::Codec(format!("invalid to address of {} bytes", n))),
+    }
+}
+
+fn h256_at(rlp: &Rlp, idx: usize) -> Result<H256> {
+    let value = u256_at(rlp, idx)?;
+    let mut raw = [0u8; 32];
+    value.to_big_endian(&mut raw);
+    Ok(H256::from(raw))
+}
+
+/// keccak of the first `fields` items, optionally followed by extra items and prefixed by the type byte
+fn signing_hash(rlp: &Rlp, fields: usize, tx_type: u8, extra: &[u64]) -> Result<H256> {
+    let mut stream = RlpStream::new_list(fields + extra.len());
+    for idx in 0..fields {
+        stream.append_raw(rlp.at(idx).map_err(rlp_err)?.as_raw(), 1);
+    }
+    for e in extra {
+        stream.append(e);
+    }
+    let mut payload = vec![];
+    if tx_type != 0 {
+        payload.push(tx_type);
+    }
+    payload.extend_from_slice(&stream.out());
+    Ok(H256::from_slice(Keccak256::digest(&payload).as_slice()))
+}
+
+fn recover_sender(hash: &H256, r: &H256, s: &H256, recid: u64) -> Result<Address> {
+    let mut rs = [0u8; 64];
+    rs[..32].copy_from_slice(r.as_bytes());
+    rs[32..].copy_from_slice(s.as_bytes());
+    let signature =
+        Signature::parse_standard_slice(&rs).map_err(|e| Error::Codec(format!("invalid signature: {:?}", e)))?;
+    let recid = u8::try_from(recid)
+        .ok()
+        .and_then(|id| RecoveryId::parse(id).ok())
+        .ok_or_else(|| Error::Codec(format!("invalid recovery id {}", recid)))?;
+    let public = recover(&Message::parse(hash.as_fixed_bytes()), &signature, &recid)
+        .map_err(|e| Error::Codec(format!("signature does not recover a public key: {:?}", e)))?;
+    let address = H160::from(H256::from_slice(
+        Keccak256::digest(&public.serialize()[1..65]).as_slice(),
+    ));
+    if address.is_zero() {
+        return Err(Error::Codec("signature recovers the zero address".to_owned()));
+    }
+    Ok(address)
+}
+
+/// decode a signed transaction and recover its sender
+pub fn decode_raw_tx(raw: &[u8]) -> Result<DecodedTx> {
+    let tx_type = match raw.first() {
+        None => return Err(Error::Codec("empty transaction".to_owned())),
+        // a legacy transaction is a rlp list, whose first byte is at least 0xc0
+        Some(b) if *b >= 0xc0 => 0,
+        Some(b) if *b == 1 || *b == 2 => *b,
+        Some(b) => return Err(Error::NotSupport(format!("transaction type {}", b))),
+    };
+    let rlp = Rlp::new(if tx_type == 0 { raw } else { &raw[1..] });
+    let items = rlp.item_count().map_err(rlp_err)?;
+    let expected = match tx_type {
+        0 => 9,
+        1 => 11,
+        _ => 12,
+    };
+    if items != expected {
+        return Err(Error::Codec(format!(
+            "type {} transaction has {} fields, expect {}",
+            tx_type, items, expected
+        )));
+    }
+
+    // offset of nonce, the typed envelopes start with the chain id
+    let base = if tx_type == 0 { 0 } else { 1 };
+    let nonce = u256_at(&rlp, base)?;
+    let (gas_price, max_priority_fee_per_gas, max_fee_per_gas, fees) = match tx_type {
+        2 => (None, Some(u256_at(&rlp, 2)?), Some(u256_at(&rlp, 3)?), 2),
+        _ => (Some(u256_at(&rlp, base + 1)?), None, None, 1),
+    };
+    let gas = u256_at(&rlp, base + 1 + fees)?;
+    let to = to_at(&rlp, base + 2 + fees)?;
+    let value = u256_at(&rlp, base + 3 + fees)?;
+    let data = Bytes(
+        rlp.at(base + 4 + fees)
+            .and_then(|item| item.data())
+            .map_err(rlp_err)?
+            .to_vec(),
+    );
+    let v = u256_at(&rlp, items - 3)?.low_u64();
+    let (r, s) = (h256_at(&rlp, items - 2)?, h256_at(&rlp, items - 1)?);
+
+    let (chain_id, recid, sighash) = match tx_type {
+        0 if v >= 35 => {
+            let chain_id = (v - 35) / 2;
+            (
+                Some(chain_id),
+                (v - 35) % 2,
+                signing_hash(&rlp, 6, 0, &[chain_id, 0, 0])?,
+            )
+        }
+        0 if v == 27 || v == 28 => (None, v - 27, signing_hash(&rlp, 6, 0, &[])?),
+        0 => return Err(Error::Codec(format!("invalid legacy v {}", v))),
+        _ => (
+            Some(u256_at(&rlp, 0)?.low_u64()),
+            v,
+            signing_hash(&rlp, items - 3, tx_type, &[])?,
+        ),
+    };
+    let sender = recover_sender(&sighash, &r, &s, recid)?;
+
+    Ok(DecodedTx {
+        tx_type,
+        chain_id,
+        nonce,
+        to,
+        value,
+        gas,
+        gas_price,
+        max_fee_per_gas,
+        max_priority_fee_per_gas,
+        data,
+        v,
+        r,
+        s,
+        hash: H256::from_slice(Keccak256::digest(raw).as_slice()),
+        sender,
+    })
+}
+
+/// the function selector of a call input, with the function and its arguments if `abi` has the selector,
+/// `None` for an input shorter than a selector
+pub fn decode_input(input: &[u8], abi: Option<&Contract>) -> Option<Value> {
+    let selector = input.get(..4)?;
+    let mut decoded = json!({ "selector": format!("0x{}", hex::encode(selector)) });
+    let function = abi.and_then(|abi| abi.functions().find(|f| f.short_signature() == selector));
+    if let Some(function) = function {
+        let kinds = function.inputs.iter().map(|p| p.kind.to_string()).collect::<Vec<_>>();
+        decoded["function"] = json!(format!("{}({})", function.name, kinds.join(",")));
+        match function.decode_input(&input[4..]) {
+            Ok(tokens) => {
+                let args = function
+                    .inputs
+                    .iter()
+                    .zip(tokens)
+                    .enumerate()
+                    .map(|(idx, (param, token))| {
+                        // unnamed parameters are keyed by position
+                        let name = match param.name.as_str() {
+                            "" => idx.to_string(),
+                            name => name.to_owned(),
+                        };
+                        (name, json!(token.to_string()))
+                    })
+                    .collect::<serde_json::Map<_, _>>();
+                decoded["args"] = Value::Object(args);
+            }
+            Err(e) => decoded["error"] = json!(format!("arguments do not match the abi: {}", e)),
+        }
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// the sender of every vector, its key is 0x4646..46
+    const SENDER: &str = "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f";
+
+    fn decode(raw: &str) -> DecodedTx {
+        decode_raw_tx(&hex::decode(raw).unwrap()).unwrap()
+    }
+
+    fn check(tx: &DecodedTx, tx_type: u8, chain_id: Option<u64>, hash: &str) {
+        assert_eq!(tx.tx_type, tx_type);
+        assert_eq!(tx.chain_id, chain_id);
+        assert_eq!(tx.sender, Address::from_str(SENDER).unwrap());
+        assert_eq!(tx.hash, H256::from_str(hash).unwrap());
+        assert_eq!(tx.to, Some(Address::repeat_byte(0x35)));
+    }
+
+    #[test]
+    fn legacy_eip155() {
+        // the example of EIP-155
+        let tx = decode(
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef6134\
+             0bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3\
+             dc64214b297fb1966a3b6d83",
+        );
+        check(
+            &tx,
+            0,
+            Some(1),
+            "33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788",
+        );
+        assert_eq!(tx.nonce, U256::from(9));
+        assert_eq!(tx.gas_price, Some(U256::from(20_000_000_000u64)));
+        assert_eq!(tx.value, U256::exp10(18));
+        assert_eq!(tx.v, 37);
+    }
+
+    #[test]
+    fn legacy_pre_eip155() {
+        let tx = decode(
+            "f86c038502540be40082520894353535353535353535353535353535353535353588016345785d8a0000801ba0575c80d7\
+             756f9545f2aa68418d07953e9d3744876aee90852b98916e5488ecb1a025954feb927017038d163f2f7d9ebda3b68cb9ce\
+             3a793591ed41ce00e776d4af",
+        );
+        check(
+            &tx,
+            0,
+            None,
+            "b8517472dc7a4eecabef144b393e5809965e5f3224ecce5e11c9147d262bfc90",
+        );
+        assert_eq!(tx.v, 27);
+    }
+
+    #[test]
+    fn access_list_tx() {
+        let tx = decode(
+            "01f8a5820868048502540be4008275309435353535353535353535353535353535353535358084a9059cbbf838f7943535\
+             353535353535353535353535353535353535e1a0000000000000000000000000000000000000000000000000000000000000\
+             000101a0f9313679f31ecb1d5e646e39c75fd696a072cd1e2913337e922a05f649f36944a0256af95952684c82da4f4176\
+             1a34e375a441cceb2c1c274604e2f371b74f0fd5",
+        );
+        check(
+            &tx,
+            1,
+            Some(2152),
+            "185936b7125b167e58f4b60ccc671253ffbfd5f27827dfb5ed9577a8c3618e9d",
+        );
+        assert_eq!(tx.gas, U256::from(30_000));
+        assert_eq!(tx.data.0, vec![0xa9, 0x05, 0x9c, 0xbb]);
+    }
+
+    #[test]
+    fn dynamic_fee_tx() {
+        let tx = decode(
+            "02f87482086805843b9aca008504a817c80082520894353535353535353535353535353535353535353587038d7ea4c680\
+             0080c001a044cb1128328ec5a594aa407a1d1f78e628174011b0cb2d4449d1cf6e3ad289a9a049beec52bb5cae9760a7ac\
+             ac4b67f51e7f8af8be16547fec82b3d14594302f02",
+        );
+        check(
+            &tx,
+            2,
+            Some(2152),
+            "4150b23a3912de1ec466193a35112d23ee324a420cb2dbbd38a9f364f4e25f07",
+        );
+        assert_eq!(tx.gas_price, None);
+        assert_eq!(tx.max_priority_fee_per_gas, Some(U256::from(1_000_000_000u64)));
+        assert_eq!(tx.max_fee_per_gas, Some(U256::from(20_000_000_000u64)));
+    }
+
+    #[test]
+    fn unknown_type() {
+        assert!(matches!(decode_raw_tx(&[3, 0xc0]), Err(Error::NotSupport(_))));
+        assert!(decode_raw_tx(&[]).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt::Formatter;
+use std::fmt::{Display, Formatter};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -10,8 +10,22 @@ pub enum Error {
     TxInternalErr(InternalError),
     Io(std::io::Error),
     Db(redis::RedisError),
+    Codec(String),
     NotSupport(String),
     Unknown(String),
+    Unavailable(String),
+    /// an account cannot pay for what the command is about to send
+    InsufficientFunds(String),
+    /// a nonce above the next one of the account, the transaction would be queued behind a gap
+    NonceGap(String),
+    /// the node did not answer a request the command cannot do without
+    NodeUnreachable(String),
+    /// a log line the command was told not to skip
+    LogParse {
+        line: u64,
+        reason: String,
+    },
+    Context(String, Box<Error>),
 }
 
 #[derive(Debug)]
@@ -29,8 +43,15 @@ impl std::fmt::Display for Error {
             Error::TxInternalErr(e) => write!(f, "Internal Error:: {:?}", e),
             Error::Io(e) => write!(f, "Io error {:?}", e),
             Error::Db(e) => write!(f, "Database error {:?}", e),
+            Error::Codec(e) => write!(f, "Codec error {}", e),
             Error::NotSupport(e) => write!(f, "Not support: {}", e),
             Error::Unknown(e) => write!(f, "a unknown error happened: {}", e),
+            Error::Unavailable(e) => write!(f, "{}", e),
+            Error::InsufficientFunds(e) => write!(f, "insufficient funds: {}", e),
+            Error::NonceGap(e) => write!(f, "nonce gap: {}", e),
+            Error::NodeUnreachable(e) => write!(f, "node unreachable: {}", e),
+            Error::LogParse { line, reason } => write!(f, "cannot parse log line {}: {}", line, reason),
+            Error::Context(ctx, e) => write!(f, "{}: {}", ctx, e),
         }
     }
 }
@@ -40,6 +61,7 @@ impl std::error::Error for Error {
         match self {
             Error::Io(e) => Some(e),
             Error::Db(e) => Some(e),
+            Error::Context(_, e) => std::error::Error::source(e.as_ref()),
             _ => None,
         }
     }
@@ -56,3 +78,52 @@ impl From<redis::RedisError> for Error {
         Self::Db(e)
     }
 }
+
+impl Error {
+    /// wrap the error with a description of what was being done when it happened
+    pub fn context<C: Display>(self, ctx: C) -> Self {
+        Self::Context(ctx.to_string(), Box::new(self))
+    }
+
+    /// process exit code of a command failing with this error, 1 for the ones without a dedicated code:
+    /// 2 unsupported usage, 3 node unreachable, 4 insufficient funds, 5 nonce gap, 6 log parse error,
+    /// 7 unavailable storage
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NotSupport(_) => 2,
+            Error::NodeUnreachable(_) => 3,
+            Error::InsufficientFunds(_) => 4,
+            Error::NonceGap(_) => 5,
+            Error::LogParse { .. } => 6,
+            Error::Unavailable(_) | Error::Db(_) => 7,
+            Error::Context(_, e) => e.exit_code(),
+            _ => 1,
+        }
+    }
+
+    /// the node rejected the nonce of a transaction, the client side nonce has drifted
+    pub fn is_invalid_nonce(&self) -> bool {
+        match self {
+            Error::TxInternalErr(InternalError::InvalidNonce(_)) => true,
+            Error::Context(_, e) => e.is_invalid_nonce(),
+            _ => false,
+        }
+    }
+}
+
+/// Attach context to any result whose error converts into [`Error`]
+pub trait ResultExt<T> {
+    fn context<C: Display>(self, ctx: C) -> Result<T>;
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for std::result::Result<T, E> {
+    fn context<C: Display>(self, ctx: C) -> Result<T> {
+        self.map_err(|e| e.into().context(ctx))
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.map_err(|e| e.into().context(f()))
+    }
+}
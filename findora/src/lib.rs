@@ -1,7 +1,10 @@
+pub mod decode;
 pub mod error;
+pub mod transport;
 pub mod utils;
 use crate::{
     error::{Error, InternalError, Result},
+    transport::{http_pool_size, HttpTransport, NodeTransport, RemapTransport},
     utils::extract_keypair_from_file,
 };
 use anyhow::bail;
@@ -21,7 +24,7 @@ use std::{
     fs,
     future::Future,
     ops::AddAssign,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{
         atomic::{AtomicU32, AtomicUsize, Ordering},
@@ -36,16 +39,20 @@ use web3::{
     self,
     api::Eth,
     contract::{tokens::Tokenizable, Contract, Options},
-    ethabi::{Int, Token, Uint},
-    transports::Http,
+    ethabi::{Int, ParamType, Token, Uint},
     types::{
-        Address, Block, BlockId, BlockNumber, Bytes, Transaction, TransactionId, TransactionParameters,
-        TransactionReceipt, H160, H256, U128, U256, U64,
+        AccessList, Address, Block, BlockId, BlockNumber, Bytes, CallRequest, Filter, Log, Transaction, TransactionId,
+        TransactionParameters, TransactionReceipt, H160, H256, U128, U256, U64,
     },
+    BatchTransport, Transport,
 };
 
 const FRC20_ADDRESS: u64 = 0x1000;
 pub const BLOCK_TIME: u64 = 16;
+/// gas used by a plain value transfer
+pub const TRANSFER_GAS: u64 = 21000;
+/// json-rpc calls sent in one batch request
+const BATCH_SIZE: usize = 100;
 
 //const WEB3_SRV: &str = "http://127.0.0.1:8545";
 //const WEB3_SRV: &str = "http://18.236.205.22:8545";
@@ -71,14 +78,284 @@ pub struct KeyPair {
     pub private: String,
 }
 
+/// version of the key files written by this tool
+pub const KEY_FILE_VERSION: u32 = 1;
+
+/// Canonical key file, a header followed by the keys:
+///
+/// ```json
+/// {"version": 1, "chain_id": 2152, "index_base": 0, "count": 1, "created": "2022-04-07T02:17:07Z",
+///  "keys": [{"index": 0, "address": "0x...", "private": "..."}]}
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyFile {
+    pub version: u32,
+    /// chain the accounts were generated for, none if unknown
+    pub chain_id: Option<u64>,
+    /// index of the first key, its derivation index for keys derived from a mnemonic
+    #[serde(default)]
+    pub index_base: u64,
+    /// number of keys, checked against the entries on load
+    pub count: u64,
+    /// creation time, RFC 3339
+    pub created: String,
+    pub keys: Vec<KeyEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyEntry {
+    pub index: u64,
+    pub address: String,
+    pub private: String,
+}
+
+impl KeyFile {
+    pub fn new(chain_id: Option<u64>, index_base: u64, keys: &[KeyPair]) -> Self {
+        let mut file = Self {
+            version: KEY_FILE_VERSION,
+            chain_id,
+            index_base,
+            count: 0,
+            created: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            keys: vec![],
+        };
+        file.extend(keys);
+        file
+    }
+
+    /// add keys after the existing ones
+    pub fn extend(&mut self, keys: &[KeyPair]) {
+        let start = self.index_base + self.keys.len() as u64;
+        self.keys.extend(keys.iter().enumerate().map(|(i, k)| KeyEntry {
+            index: start + i as u64,
+            address: k.address.clone(),
+            private: k.private.clone(),
+        }));
+        self.count = self.keys.len() as u64;
+    }
+
+    /// check the header before trusting the entries
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.version == 0 || self.version > KEY_FILE_VERSION {
+            bail!(
+                "unsupported key file version {}, this tool reads up to {}",
+                self.version,
+                KEY_FILE_VERSION
+            );
+        }
+        if self.count != self.keys.len() as u64 {
+            bail!(
+                "key file declares {} keys but holds {}, it may be truncated",
+                self.count,
+                self.keys.len()
+            );
+        }
+        Ok(())
+    }
+
+    pub fn key_pairs(&self) -> Vec<KeyPair> {
+        self.keys
+            .iter()
+            .map(|k| KeyPair {
+                address: k.address.clone(),
+                private: k.private.clone(),
+            })
+            .collect()
+    }
+
+    /// write through a temporary file, a crash never leaves a half written key file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        save_json(path, self)
+    }
+}
+
+/// write json through a temporary file renamed over `path`
+fn save_json<P: AsRef<Path>, T: Serialize>(path: P, value: &T) -> std::io::Result<()> {
+    let mut tmp = path.as_ref().as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+    fs::rename(&tmp, path)
+}
+
+/// write keys as a new [`KeyFile`], the first one at `index_base`
+pub fn write_keys<P: AsRef<Path>>(
+    path: P,
+    chain_id: Option<u64>,
+    index_base: u64,
+    keys: &[KeyPair],
+) -> std::io::Result<()> {
+    KeyFile::new(chain_id, index_base, keys).save(path)
+}
+
+/// append keys to a key file, a missing or empty file becomes a [`KeyFile`] starting at `index_base`,
+/// a json array gets the keys in the layout of its elements, hex keys or key pairs
+pub fn append_keys<P: AsRef<Path>>(
+    path: P,
+    chain_id: Option<u64>,
+    index_base: u64,
+    keys: &[KeyPair],
+) -> std::io::Result<()> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    if content.trim().is_empty() {
+        return write_keys(path, chain_id, index_base, keys);
+    }
+    if content.trim_start().starts_with('{') {
+        let mut file: KeyFile = serde_json::from_str(&content)?;
+        file.extend(keys);
+        return file.save(path);
+    }
+    let mut array: Vec<serde_json::Value> = serde_json::from_str(&content)?;
+    let hex_keys = !array.is_empty() && array.iter().all(serde_json::Value::is_string);
+    for key in keys {
+        array.push(if hex_keys {
+            serde_json::Value::String(key.private.clone())
+        } else {
+            serde_json::to_value(key)?
+        });
+    }
+    save_json(path, &array)
+}
+
+/// Layout of a key file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// versioned [`KeyFile`], written by this tool
+    Versioned(u32),
+    /// json array of `{"address", "private"}` objects, written by older versions
+    KeyPairs,
+    /// json array of hex private keys
+    JsonArray,
+    /// one hex private key per line
+    HexLines,
+}
+
+impl std::fmt::Display for KeyFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = match self {
+            Self::Versioned(version) => return write!(f, "key file v{}", version),
+            Self::KeyPairs => "json key pairs",
+            Self::JsonArray => "json array of hex keys",
+            Self::HexLines => "hex keys per line",
+        };
+        write!(f, "{}", format)
+    }
+}
+
+/// load keys from a file, the format is detected from the content
+pub fn load_keys<P: AsRef<Path> + std::fmt::Debug>(path: P) -> anyhow::Result<Vec<KeyPair>> {
+    load_keys_with_chain(path).map(|(keys, _)| keys)
+}
+
+/// load keys from a file along with the chain id of its header, none if the format has no header
+pub fn load_keys_with_chain<P: AsRef<Path> + std::fmt::Debug>(path: P) -> anyhow::Result<(Vec<KeyPair>, Option<u64>)> {
+    let content = fs::read_to_string(&path)?;
+    let (format, chain_id, keys) = parse_keys(&content)?;
+    info!("{} keys loaded from {:?}, format: {}", keys.len(), path, format);
+    Ok((keys, chain_id))
+}
+
+/// parse keys in any [`KeyFormat`], hex keys may have a `0x` prefix and `#` starts a comment line,
+/// the chain id comes from the header of a versioned key file
+pub fn parse_keys(content: &str) -> anyhow::Result<(KeyFormat, Option<u64>, Vec<KeyPair>)> {
+    let content = content.trim();
+    if content.starts_with('{') {
+        let file: KeyFile =
+            serde_json::from_str(content).map_err(|e| anyhow::anyhow!("invalid versioned key file: {}", e))?;
+        file.validate()?;
+        Ok((KeyFormat::Versioned(file.version), file.chain_id, file.key_pairs()))
+    } else if content.starts_with('[') {
+        if let Ok(keys) = serde_json::from_str::<Vec<KeyPair>>(content) {
+            return Ok((KeyFormat::KeyPairs, None, keys));
+        }
+        let secrets: Vec<String> =
+            serde_json::from_str(content).map_err(|e| anyhow::anyhow!("invalid json key file: {}", e))?;
+        let keys = secrets.iter().map(|s| key_from_hex(s)).collect::<anyhow::Result<_>>()?;
+        Ok((KeyFormat::JsonArray, None, keys))
+    } else {
+        let keys = content
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(key_from_hex)
+            .collect::<anyhow::Result<_>>()?;
+        Ok((KeyFormat::HexLines, None, keys))
+    }
+}
+
+fn key_from_hex(secret: &str) -> anyhow::Result<KeyPair> {
+    let raw = hex::decode(secret.trim().trim_start_matches("0x"))?;
+    let secret = SecretKey::parse_slice(&raw).map_err(|e| anyhow::anyhow!("invalid private key: {:?}", e))?;
+    Ok(key_pair(&secret))
+}
+
 #[inline(always)]
 pub fn one_eth_key() -> KeyPair {
     let mnemonic = Mnemonic::generate_in(Language::English, Count::Words12);
     let bs = mnemonic.to_seed("");
-    let ext = XPrv::derive_from_path(&bs, &DerivationPath::from_str("m/44'/60'/0'/0/0").unwrap()).unwrap();
+    derive_eth_key(&bs, 0)
+}
+
+/// derive `count` sequential accounts `m/44'/60'/0'/0/i` from a mnemonic phrase, `i` from `index_base`
+pub fn derive_eth_keys(phrase: &str, index_base: u64, count: u64) -> anyhow::Result<Vec<KeyPair>> {
+    let mnemonic = Mnemonic::from_phrase_in(Language::English, phrase.trim())
+        .map_err(|e| anyhow::anyhow!("invalid mnemonic: {:?}", e))?;
+    let end = index_base
+        .checked_add(count)
+        .filter(|end| *end <= 1 << 31)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "account index {} + {} is out of the non-hardened range",
+                index_base,
+                count
+            )
+        })?;
+    let bs = mnemonic.to_seed("");
+    Ok((index_base..end).map(|i| derive_eth_key(&bs, i)).collect())
+}
+
+/// decrypt a web3 secret storage keystore, or every `.json` keystore of a directory such as geth's
+pub fn load_keystore<P: AsRef<Path> + std::fmt::Debug>(path: P, password: &str) -> anyhow::Result<Vec<KeyPair>> {
+    let files = if path.as_ref().is_dir() {
+        let mut files = fs::read_dir(&path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        files.retain(|f| f.is_file());
+        files.sort();
+        files
+    } else {
+        vec![path.as_ref().to_path_buf()]
+    };
+    let keys = files
+        .iter()
+        .map(|file| {
+            let secret = eth_keystore::decrypt_key(file, password)
+                .map_err(|e| anyhow::anyhow!("invalid keystore {:?}: {}", file, e))?;
+            let secret = SecretKey::parse_slice(&secret)
+                .map_err(|e| anyhow::anyhow!("invalid key in keystore {:?}: {:?}", file, e))?;
+            Ok(key_pair(&secret))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    info!("{} keys decrypted from {:?}", keys.len(), path);
+    Ok(keys)
+}
+
+fn derive_eth_key(seed: &[u8], index: u64) -> KeyPair {
+    let path = format!("m/44'/60'/0'/0/{}", index);
+    let ext = XPrv::derive_from_path(seed, &DerivationPath::from_str(path.as_str()).unwrap()).unwrap();
 
     let secret = SecretKey::parse_slice(&ext.to_bytes()).unwrap();
-    let public = PublicKey::from_secret_key(&secret);
+    key_pair(&secret)
+}
+
+fn key_pair(secret: &SecretKey) -> KeyPair {
+    let public = PublicKey::from_secret_key(secret);
 
     let mut res = [0u8; 64];
     res.copy_from_slice(&public.serialize()[1..65]);
@@ -107,14 +384,102 @@ pub struct TransferMetrics {
     pub txs: Vec<TxMetric>,
 }
 
+/// Transaction envelope sent by load tests
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxType {
+    Legacy,
+    /// EIP-2930
+    AccessList(AccessList),
+    /// EIP-1559, with the priority fee, the gas price if none
+    DynamicFee(Option<U256>),
+}
+
+impl std::str::FromStr for TxType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "legacy" => Ok(Self::Legacy),
+            "2930" => Ok(Self::AccessList(vec![])),
+            "1559" => Ok(Self::DynamicFee(None)),
+            _ => Err("Invalid tx type: legacy, 2930 and 1559 are supported".to_owned()),
+        }
+    }
+}
+
+impl std::fmt::Display for TxType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tx_type = match self {
+            Self::Legacy => "legacy",
+            Self::AccessList(_) => "2930",
+            Self::DynamicFee(_) => "1559",
+        };
+        write!(f, "{}", tx_type)
+    }
+}
+
+impl TxType {
+    /// fill in the type specific fields, the gas price caps the fee of EIP-1559 transactions
+    pub fn apply(&self, tx: &mut TransactionParameters, gas_price: Option<U256>) {
+        match self {
+            Self::Legacy => tx.gas_price = gas_price,
+            Self::AccessList(list) => {
+                tx.transaction_type = Some(U64::from(1u64));
+                tx.access_list = Some(list.clone());
+                tx.gas_price = gas_price;
+            }
+            Self::DynamicFee(priority) => {
+                tx.transaction_type = Some(U64::from(2u64));
+                tx.max_fee_per_gas = gas_price;
+                tx.max_priority_fee_per_gas = match (priority, gas_price) {
+                    (Some(priority), Some(cap)) => Some((*priority).min(cap)),
+                    (priority, cap) => cap.or(*priority),
+                };
+            }
+        }
+    }
+
+    /// minimum gas of a transaction carrying `data`, charged before any execution (EIP-2028, EIP-2930)
+    pub fn intrinsic_gas(&self, data: &[u8]) -> u64 {
+        let data_gas = data.iter().map(|b| if *b == 0 { 4 } else { 16 }).sum::<u64>();
+        let access_list_gas = match self {
+            Self::AccessList(list) => list
+                .iter()
+                .map(|item| 2400 + 1900 * item.storage_keys.len() as u64)
+                .sum::<u64>(),
+            Self::Legacy | Self::DynamicFee(_) => 0,
+        };
+        TRANSFER_GAS + data_gas + access_list_gas
+    }
+}
+
+/// How to wait for transaction receipts
+#[derive(Debug, Clone, Copy)]
+pub struct ConfirmOpts {
+    pub poll_interval: Duration,
+    pub timeout: Duration,
+}
+
+impl ConfirmOpts {
+    /// poll every `poll_interval` milliseconds for at most `timeout` seconds, three blocks by default
+    pub fn new(block_time: u64, poll_interval: u64, timeout: Option<u64>) -> Self {
+        Self {
+            poll_interval: Duration::from_millis(poll_interval),
+            timeout: Duration::from_secs(timeout.unwrap_or(block_time * 3 + 1)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TestClient {
-    pub web3: Arc<web3::Web3<Http>>,
-    pub eth: Arc<web3::api::Eth<Http>>,
-    pub accounts: Arc<web3::api::Accounts<Http>>,
+    pub web3: Arc<web3::Web3<NodeTransport>>,
+    pub eth: Arc<web3::api::Eth<NodeTransport>>,
+    pub accounts: Arc<web3::api::Accounts<NodeTransport>>,
     pub root_sk: secp256k1::SecretKey,
     pub root_addr: Address,
     pub overflow_flag: AtomicUsize,
+    /// gas limit of the transfers sent by `distribution` and `distribution_simple`
+    pub gas_limit: U256,
     rt: Runtime,
 }
 
@@ -127,17 +492,29 @@ pub struct NetworkInfo {
 }
 
 impl TestClient {
-    pub fn setup(url: Option<String>, timeout: Option<u64>) -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(timeout.unwrap_or(3)))
-            .build()
-            .unwrap();
+    pub fn setup(url: Option<String>, timeout: Option<u64>, node_timeout: Option<u64>) -> Self {
+        Self::setup_with_root(url, timeout, node_timeout, None)
+    }
+
+    /// set up a client paying with the given root key, the `.secret` file is read if none
+    pub fn setup_with_root(
+        url: Option<String>,
+        timeout: Option<u64>,
+        node_timeout: Option<u64>,
+        root: Option<(secp256k1::SecretKey, Address)>,
+    ) -> Self {
+        let mut builder = Client::builder().timeout(Duration::from_secs(timeout.unwrap_or(3)));
+        if let Some(node_timeout) = node_timeout {
+            builder = builder.connect_timeout(Duration::from_secs(node_timeout));
+        }
+        builder = builder.pool_max_idle_per_host(http_pool_size());
+        let client = builder.build().unwrap();
         let url = Url::parse(url.as_deref().unwrap_or(WEB3_SRV)).unwrap();
-        let transport = Http::with_client(client, url);
+        let transport = RemapTransport::new(HttpTransport::new(client, url));
         let web3 = Arc::new(web3::Web3::new(transport));
         let eth = Arc::new(web3.eth());
         let accounts = Arc::new(web3.accounts());
-        let (root_sk, root_addr) = extract_keypair_from_file(".secret");
+        let (root_sk, root_addr) = root.unwrap_or_else(|| extract_keypair_from_file(".secret"));
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
@@ -151,6 +528,7 @@ impl TestClient {
             root_addr,
             rt,
             overflow_flag: AtomicUsize::from(0),
+            gas_limit: U256::from(TRANSFER_GAS),
         }
     }
 
@@ -162,6 +540,14 @@ impl TestClient {
         self.rt.block_on(self.eth.block_number()).ok()
     }
 
+    /// transactions in the pending block of the node, a hint of its mempool size
+    pub fn pending_tx_count(&self) -> Option<U256> {
+        self.rt
+            .block_on(self.eth.block_transaction_count(BlockId::Number(BlockNumber::Pending)))
+            .ok()
+            .flatten()
+    }
+
     pub fn current_block(&self) -> Option<Block<H256>> {
         self.rt
             .block_on(self.eth.block(BlockId::Number(BlockNumber::Latest)))
@@ -224,6 +610,16 @@ impl TestClient {
         self.rt.block_on(self.eth.gas_price()).ok()
     }
 
+    /// priority fee suggested by the node, none if it does not support `eth_maxPriorityFeePerGas`
+    pub fn max_priority_fee(&self) -> Option<U256> {
+        let value = self
+            .rt
+            .block_on(self.web3.transport().execute("eth_maxPriorityFeePerGas", vec![]))
+            .map_err(|e| debug!("eth_maxPriorityFeePerGas failed: {}", e))
+            .ok()?;
+        serde_json::from_value(value).ok()
+    }
+
     pub fn frc20_code(&self) -> Option<Bytes> {
         self.rt
             .block_on(self.eth.code(H160::from_low_u64_be(FRC20_ADDRESS), None))
@@ -235,6 +631,60 @@ impl TestClient {
         self.rt.block_on(self.eth.transaction(id)).unwrap_or_default()
     }
 
+    pub fn estimate_gas(&self, req: CallRequest) -> web3::Result<U256> {
+        self.rt.block_on(self.eth.estimate_gas(req, None))
+    }
+
+    pub fn logs(&self, filter: Filter) -> web3::Result<Vec<Log>> {
+        self.rt.block_on(self.eth.logs(filter))
+    }
+
+    /// all receipts of a block in one `eth_getBlockReceipts` call, which not every node supports
+    pub fn block_receipts(&self, number: U64) -> web3::Result<Vec<TransactionReceipt>> {
+        let value = self.rt.block_on(
+            self.web3
+                .transport()
+                .execute("eth_getBlockReceipts", vec![serde_json::json!(number)]),
+        )?;
+        serde_json::from_value::<Option<Vec<TransactionReceipt>>>(value)
+            .map(Option::unwrap_or_default)
+            .map_err(|e| web3::Error::Decoder(e.to_string()))
+    }
+
+    /// latest balances and pending nonces of accounts, batched, none for the failed calls
+    pub fn balances_and_nonces(&self, addresses: &[Address]) -> Vec<(Option<U256>, Option<U256>)> {
+        let transport = self.web3.transport();
+        addresses
+            .chunks(BATCH_SIZE / 2)
+            .flat_map(|chunk| {
+                let requests = chunk
+                    .iter()
+                    .flat_map(|address| {
+                        let address = serde_json::json!(address);
+                        [
+                            transport.prepare("eth_getBalance", vec![address.clone(), "latest".into()]),
+                            transport.prepare("eth_getTransactionCount", vec![address, "pending".into()]),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                let values = match self.rt.block_on(transport.send_batch(requests)) {
+                    Ok(values) => values,
+                    Err(e) => {
+                        warn!("batch of {} accounts failed: {:?}", chunk.len(), e);
+                        vec![]
+                    }
+                };
+                let mut values = values
+                    .into_iter()
+                    .map(|v| v.ok().and_then(|v| serde_json::from_value::<U256>(v).ok()));
+                chunk
+                    .iter()
+                    .map(|_| (values.next().flatten(), values.next().flatten()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
     pub fn transaction_receipt(&self, hash: H256) -> Option<TransactionReceipt> {
         self.rt.block_on(self.eth.transaction_receipt(hash)).unwrap_or_default()
     }
@@ -265,6 +715,74 @@ impl TestClient {
         }
     }
 
+    /// poll for a receipt until it shows up or the confirmation timeout elapses
+    pub fn wait_for_receipt(&self, hash: H256, confirm: &ConfirmOpts) -> Option<TransactionReceipt> {
+        let deadline = time::Instant::now() + confirm.timeout;
+        loop {
+            if let Some(receipt) = self.transaction_receipt(hash) {
+                break Some(receipt);
+            }
+            if time::Instant::now() >= deadline {
+                break None;
+            }
+            std::thread::sleep(confirm.poll_interval);
+        }
+    }
+
+    /// poll for a receipt like [`TestClient::wait_for_receipt`], also noting when the node first reports the
+    /// transaction as pending, never if the node only shows it once mined
+    pub fn wait_for_inclusion(
+        &self,
+        hash: H256,
+        confirm: &ConfirmOpts,
+    ) -> (Option<TransactionReceipt>, Option<time::Instant>) {
+        let deadline = time::Instant::now() + confirm.timeout;
+        let (mut pending, mut looking) = (None, true);
+        loop {
+            if looking {
+                match self.transaction(TransactionId::from(hash)) {
+                    Some(tx) if tx.block_number.is_none() => {
+                        pending = Some(time::Instant::now());
+                        looking = false;
+                    }
+                    Some(_) => looking = false,
+                    None => {}
+                }
+            }
+            if let Some(receipt) = self.transaction_receipt(hash) {
+                break (Some(receipt), pending);
+            }
+            if time::Instant::now() >= deadline {
+                break (None, pending);
+            }
+            std::thread::sleep(confirm.poll_interval);
+        }
+    }
+
+    /// replay a mined transaction with `eth_call` on the state before its block to recover its revert
+    /// payload, receipts do not keep it, none if the node returns no revert data
+    pub fn revert_reason(&self, hash: H256) -> Option<String> {
+        let tx = self.transaction(TransactionId::from(hash))?;
+        let block = tx.block_number?.saturating_sub(U64::one());
+        let req = CallRequest {
+            from: tx.from,
+            to: tx.to,
+            gas: Some(tx.gas),
+            gas_price: tx.gas_price,
+            value: Some(tx.value),
+            data: Some(tx.input),
+            ..Default::default()
+        };
+        match self
+            .rt
+            .block_on(self.eth.call(req, Some(BlockId::Number(BlockNumber::Number(block)))))
+        {
+            Ok(data) if data.0.is_empty() => None,
+            Ok(data) => Some(describe_revert(&data.0)),
+            Err(e) => revert_data(&e).map(|data| describe_revert(&data)),
+        }
+    }
+
     pub fn check_wait_overflow(&self, id: usize, interval: Option<u64>) {
         loop {
             let flag = self.overflow_flag.load(Ordering::Relaxed);
@@ -291,6 +809,9 @@ impl TestClient {
                     } else {
                         Error::TxInternalErr(InternalError::Other(err_str))
                     }
+                } else if err_str.contains("nonce too low") {
+                    // geth style rejection of a nonce already used
+                    Error::TxInternalErr(InternalError::InvalidNonce(err_str))
                 } else {
                     Error::Unknown(err_str)
                 }
@@ -304,19 +825,19 @@ impl TestClient {
         id: usize,
         source: Option<(secp256k1::SecretKey, Address)>,
         targets: &[(Address, U256)],
-        block_time: &Option<u64>,
+        confirm: &ConfirmOpts,
         need_wait: bool,
         need_retry: bool,
+        start_nonce: Option<U256>,
     ) -> Result<TransferMetrics> {
         let mut results = vec![];
         let mut succeed = 0u64;
         let total = targets.len();
         let source_address = source.unwrap_or((self.root_sk, self.root_addr)).1;
         let source_sk = source.unwrap_or((self.root_sk, self.root_addr)).0;
-        let wait_time = block_time.unwrap_or(BLOCK_TIME) * 3 + 1;
         let chain_id = self.chain_id().map(|id| id.as_u64());
         let gas_price = self.gas_price();
-        let nonce = RefCell::new(self.pending_nonce(source_address).unwrap());
+        let nonce = RefCell::new(start_nonce.unwrap_or_else(|| self.pending_nonce(source_address).unwrap()));
         let last_err_cnt = RefCell::new(0u64);
         targets
             .iter()
@@ -332,6 +853,7 @@ impl TestClient {
                     to,
                     value: *am,
                     chain_id,
+                    gas: self.gas_limit,
                     gas_price,
                     nonce: Some(*nonce.borrow()),
                     ..Default::default()
@@ -504,28 +1026,25 @@ impl TestClient {
 
         info!("Waiting for final results...");
 
+        let mut timed_out = 0u64;
         results.iter_mut().enumerate().for_each(|(idx, metric)| {
-            let mut retry = wait_time;
-            loop {
-                if let Some(hash) = metric.hash {
-                    if let Some(receipt) = self.transaction_receipt(hash) {
+            if let Some(hash) = metric.hash {
+                let begin = time::Instant::now();
+                match self.wait_for_receipt(hash, confirm) {
+                    Some(receipt) => {
                         if let Some(status) = receipt.status {
                             if status == U64::from(1u64) {
                                 succeed += 1;
                                 metric.status = 1;
                             }
                         }
-                        metric.wait = wait_time + 1 - retry;
-                        break;
-                    } else {
-                        std::thread::sleep(Duration::from_secs(1));
-                        retry -= 1;
-                        if retry == 0 {
-                            metric.wait = wait_time;
-                            break;
-                        }
+                    }
+                    None => {
+                        timed_out += 1;
+                        warn!("no receipt of {:?} within {:?}", hash, confirm.timeout);
                     }
                 }
+                metric.wait = begin.elapsed().as_secs();
             }
             log::info!(
                 "{}/{} {:?} {:?} {}",
@@ -538,6 +1057,12 @@ impl TestClient {
         });
 
         info!("Tx succeeded: {}/{}", succeed, total);
+        if timed_out > 0 {
+            warn!(
+                "Tx not confirmed within {:?}: {}/{}, polled every {:?}",
+                confirm.timeout, timed_out, total, confirm.poll_interval
+            );
+        }
 
         Ok(TransferMetrics {
             from: source_address,
@@ -547,6 +1072,7 @@ impl TestClient {
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn distribution_simple(
         &self,
         source: &secp256k1::SecretKey,
@@ -554,25 +1080,94 @@ impl TestClient {
         chain_id: Option<u64>,
         gas_price: Option<U256>,
         nonce: Option<U256>,
+        tx_type: &TxType,
+        data: Bytes,
+    ) -> Result<H256> {
+        let raw = self.sign_simple(source, target, chain_id, gas_price, nonce, tx_type, data)?;
+        self.send_raw(raw)
+    }
+
+    /// send a transfer like [`TestClient::distribution_simple`] with its own gas limit
+    #[allow(clippy::too_many_arguments)]
+    pub fn distribution_with_gas(
+        &self,
+        source: &secp256k1::SecretKey,
+        target: &(Address, U256),
+        gas: U256,
+        chain_id: Option<u64>,
+        gas_price: Option<U256>,
+        nonce: Option<U256>,
+        tx_type: &TxType,
+        data: Bytes,
     ) -> Result<H256> {
+        let raw = self.sign_with_gas(source, target, gas, chain_id, gas_price, nonce, tx_type, data)?;
+        self.send_raw(raw)
+    }
+
+    /// send a signed transaction, one the node already has ("already known") was sent before and keeps its nonce,
+    /// its hash is the hash of the raw bytes
+    fn send_raw(&self, raw: Bytes) -> Result<H256> {
+        let hash = H256::from(web3::signing::keccak256(&raw.0));
+        match self.rt.block_on(self.eth.send_raw_transaction(raw)) {
+            Ok(hash) => Ok(hash),
+            Err(e) if e.to_string().contains("already known") => {
+                debug!("{:?} already known by the node", hash);
+                Ok(hash)
+            }
+            Err(e) => Err(self.parse_error(e.source())),
+        }
+    }
+
+    /// sign a transfer without sending it, no request is made if chain id, gas price and nonce are given
+    #[allow(clippy::too_many_arguments)]
+    pub fn sign_simple(
+        &self,
+        source: &secp256k1::SecretKey,
+        target: &(Address, U256),
+        chain_id: Option<u64>,
+        gas_price: Option<U256>,
+        nonce: Option<U256>,
+        tx_type: &TxType,
+        data: Bytes,
+    ) -> Result<Bytes> {
+        self.sign_with_gas(
+            source,
+            target,
+            self.gas_limit,
+            chain_id,
+            gas_price,
+            nonce,
+            tx_type,
+            data,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn sign_with_gas(
+        &self,
+        source: &secp256k1::SecretKey,
+        target: &(Address, U256),
+        gas: U256,
+        chain_id: Option<u64>,
+        gas_price: Option<U256>,
+        nonce: Option<U256>,
+        tx_type: &TxType,
+        data: Bytes,
+    ) -> Result<Bytes> {
         let (account, amount) = target;
-        let tx_object = TransactionParameters {
+        let mut tx_object = TransactionParameters {
             to: Some(*account),
             value: *amount,
+            data,
+            gas,
             chain_id,
-            gas_price,
             nonce,
             ..Default::default()
         };
+        tx_type.apply(&mut tx_object, gas_price);
         // Sign the txs (can be done offline)
         match self.rt.block_on(self.accounts.sign_transaction(tx_object, source)) {
-            Ok(signed) => {
-                let result = self.rt.block_on(self.eth.send_raw_transaction(signed.raw_transaction));
-                match result {
-                    Err(e) => Err(self.parse_error(e.source())),
-                    Ok(hash) => Ok(hash),
-                }
-            }
+            Ok(signed) => Ok(signed.raw_transaction),
             Err(e) => Err(self.parse_error(e.source())),
         }
     }
@@ -749,6 +1344,50 @@ pub struct QueryJson {
     pub args: String,
 }
 
+/// decode the reason string of an `Error(string)` revert payload
+pub fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+    if data.len() < 4 || data[..4] != ERROR_SELECTOR {
+        return None;
+    }
+    match web3::ethabi::decode(&[ParamType::String], &data[4..]).ok()?.pop()? {
+        Token::String(reason) => Some(reason),
+        _ => None,
+    }
+}
+
+/// readable revert payload: the `Error(string)` reason, the `Panic(uint256)` code,
+/// or the selector and raw data of a custom error
+pub fn describe_revert(data: &[u8]) -> String {
+    const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+    if let Some(reason) = decode_revert_reason(data) {
+        return reason;
+    }
+    match data.len() {
+        0 => "no revert data".to_owned(),
+        1..=3 => format!("revert data 0x{}", hex::encode(data)),
+        4 => format!("custom error 0x{}", hex::encode(data)),
+        36 if data[..4] == PANIC_SELECTOR => format!("panic 0x{:x}", U256::from_big_endian(&data[4..])),
+        _ => format!(
+            "custom error 0x{}, data 0x{}",
+            hex::encode(&data[..4]),
+            hex::encode(&data[4..])
+        ),
+    }
+}
+
+/// revert payload carried by the error of a reverted `eth_call` or `eth_estimateGas`
+pub fn revert_data(e: &web3::Error) -> Option<Vec<u8>> {
+    match e {
+        web3::Error::Rpc(rpc) => rpc
+            .data
+            .as_ref()
+            .and_then(|d| d.as_str())
+            .and_then(|d| hex::decode(d.trim_start_matches("0x")).ok()),
+        _ => None,
+    }
+}
+
 pub fn parse_deploy_json(pat: &PathBuf) -> anyhow::Result<DeployJson> {
     let deploy_json_bytes = fs::read(pat)?;
     let deply_json_obj: DeployJson = serde_json::from_slice(deploy_json_bytes.as_slice())?;
@@ -807,7 +1446,7 @@ fn parse_args_csv(args: &str) -> anyhow::Result<Vec<Token>> {
 }
 
 async fn contract_deploy(
-    eth: Eth<Http>,
+    eth: Eth<NodeTransport>,
     sec_key: &str,
     code_path: &str,
     abi_path: &str,
@@ -849,7 +1488,7 @@ async fn contract_deploy(
 }
 
 async fn contract_call(
-    eth: Eth<Http>,
+    eth: Eth<NodeTransport>,
     contr_addr: &str,
     sec_key: &str,
     // _account: &str,
@@ -880,7 +1519,7 @@ async fn contract_call(
 }
 
 async fn contract_query(
-    eth: Eth<Http>,
+    eth: Eth<NodeTransport>,
     contr_addr: &str,
     // _account: &str,
     abi_path: &str,
@@ -1014,3 +1653,54 @@ fn get_timestamp() -> u128 {
         Err(_) => panic!("SystemTime before UNIX EPOCH!"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_to_json_arrays() {
+        let path = std::env::temp_dir().join(format!("feth-array-keys-{}.json", std::process::id()));
+        let keys = (0..2).map(|_| one_eth_key()).collect::<Vec<_>>();
+
+        fs::write(&path, "[]").unwrap();
+        append_keys(&path, None, 0, &keys).unwrap();
+        let loaded: Vec<KeyPair> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(loaded, keys);
+
+        let existing = one_eth_key();
+        fs::write(&path, format!("{}\n", serde_json::to_string(&[&existing]).unwrap())).unwrap();
+        append_keys(&path, None, 0, &keys).unwrap();
+        let loaded: Vec<KeyPair> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, [vec![existing], keys].concat());
+    }
+
+    #[test]
+    fn chunked_keys_reload() {
+        let path = std::env::temp_dir().join(format!("feth-keys-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let chunks = (0..3)
+            .map(|_| (0..4).map(|_| one_eth_key()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        for chunk in &chunks {
+            append_keys(&path, Some(2152), 10, chunk).unwrap();
+        }
+        let (keys, chain_id) = load_keys_with_chain(&path).unwrap();
+        let file: KeyFile = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(chain_id, Some(2152));
+        assert_eq!(file.count, 12);
+        assert_eq!(
+            file.keys.iter().map(|k| k.index).collect::<Vec<_>>(),
+            (10..22).collect::<Vec<_>>()
+        );
+        let expected = chunks.concat();
+        assert_eq!(keys.len(), expected.len());
+        for (loaded, written) in keys.iter().zip(&expected) {
+            assert_eq!(loaded.address, written.address);
+            assert_eq!(loaded.private, written.private);
+        }
+    }
+}
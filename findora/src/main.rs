@@ -1,45 +1,330 @@
+mod adaptive;
+mod assertion;
+mod baseline;
+mod checkpoint;
+mod color;
 mod commands;
 mod db;
+mod matrix;
+mod metrics;
+mod output;
 mod profiler;
+mod report;
+mod stats;
+mod template;
+mod tui;
+mod workload;
 
 use std::{
     cell::RefCell,
     cmp::Ordering,
+    collections::{HashMap, HashSet},
     ops::{Mul, MulAssign, Sub},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{
         atomic::{AtomicU64, Ordering::Relaxed},
-        mpsc, Arc,
+        mpsc, Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+use adaptive::Aimd;
+use checkpoint::{Checkpoint, CHECKPOINT_INTERVAL};
 use commands::*;
-use feth::{one_eth_key, parse_call_json, parse_deploy_json, parse_query_json, utils::*, KeyPair, TestClient};
-use log::{debug, error, info};
+use feth::{
+    append_keys,
+    decode::{decode_input, decode_raw_tx},
+    derive_eth_keys, describe_revert,
+    error::{Error, ResultExt},
+    load_keys, load_keys_with_chain, load_keystore, one_eth_key, parse_call_json, parse_deploy_json, parse_query_json,
+    revert_data,
+    transport::{set_backoff_jitter, set_http_pool_size, set_rpc_overrides, RateLimitReport},
+    utils::*,
+    write_keys, ConfirmOpts, TestClient, TransferMetrics, TxType, TRANSFER_GAS,
+};
+use futures::StreamExt;
+use log::{debug, error, info, warn};
+use metrics::{GasBudget, GasPrices, Metrics, Outcome, SummaryGuard};
+use output::{HashEncoding, Output};
 use rayon::prelude::*;
-use web3::types::{Address, Block, BlockId, BlockNumber, TransactionId, H256, U256, U64};
+use serde_json::{json, Value};
+use tui::{Dashboard, LogTail};
+use web3::types::{
+    Address, Block, BlockId, BlockNumber, Bytes, CallRequest, FilterBuilder, TransactionId, TransactionReceipt, H256,
+    U256, U64,
+};
+use workload::Workload;
 
-fn eth_transaction(network: &str, timeout: Option<u64>, hash: H256) {
+fn eth_transaction(
+    network: &str,
+    timeout: Option<u64>,
+    node_timeout: Option<u64>,
+    hash: H256,
+    encoding: HashEncoding,
+    abi: Option<&Path>,
+) -> anyhow::Result<()> {
+    let abi = abi
+        .map(|path| {
+            let file = std::fs::File::open(path).with_context(|| format!("while opening abi {:?}", path))?;
+            web3::ethabi::Contract::load(file).map_err(|e| anyhow::anyhow!("invalid abi {:?}: {}", path, e))
+        })
+        .transpose()?;
     let network = real_network(network);
     // use first endpoint to fund accounts
-    let client = TestClient::setup(network[0].clone(), timeout);
-    let tx = client.transaction(TransactionId::from(hash));
-    log::info!("{:?}", tx);
+    let client = TestClient::setup(network[0].clone(), timeout, node_timeout);
+    match client.transaction(TransactionId::from(hash)) {
+        Some(tx) => {
+            let mut value = serde_json::to_value(&tx).unwrap_or_default();
+            value["hash"] = json!(encoding.encode(&tx.hash));
+            if let Some(block_hash) = tx.block_hash {
+                value["blockHash"] = json!(encoding.encode(&block_hash));
+            }
+            if let Some(decoded) = decode_input(&tx.input.0, abi.as_ref()) {
+                value["decodedInput"] = decoded;
+            }
+            log::info!("{}", value);
+        }
+        None => log::info!("transaction {} not found", encoding.encode(&hash)),
+    }
+    Ok(())
 }
 
-fn eth_account(network: &str, timeout: Option<u64>, account: Address) {
+fn eth_account(
+    network: &str,
+    timeout: Option<u64>,
+    node_timeout: Option<u64>,
+    account: Address,
+    decimals: Option<usize>,
+    grouped: bool,
+) -> Vec<Value> {
     let network = real_network(network);
     // use first endpoint to fund accounts
-    let client = TestClient::setup(network[0].clone(), timeout);
+    let client = TestClient::setup(network[0].clone(), timeout, node_timeout);
     let balance = client.balance(account, None);
+    let balance = match decimals {
+        Some(decimals) => format!("{} eth", format_eth(balance, decimals, grouped)),
+        None => format_wei(balance, grouped),
+    };
     let nonce = client.nonce(account, None);
     log::info!("{:?}: {} {:?}", account, balance, nonce);
+    vec![
+        json!(format!("{:?}", account)),
+        json!(balance),
+        json!(nonce.map(|n| n.as_u64())),
+    ]
+}
+
+fn eth_watch(
+    network: &str,
+    timeout: Option<u64>,
+    node_timeout: Option<u64>,
+    accounts: &[Address],
+    threshold: u64,
+    interval: u64,
+) {
+    let mut limit = web3::types::U256::exp10(17); // 0.1 eth
+    limit.mul_assign(threshold);
+
+    let network = real_network(network);
+    let client = TestClient::setup(network[0].clone(), timeout, node_timeout);
+    info!("watching {} accounts, threshold {}", accounts.len(), limit);
+    loop {
+        let depleted = accounts
+            .iter()
+            .filter(|account| {
+                let balance = client.balance(**account, None);
+                debug!("{:?}: {}", account, balance);
+                if balance < limit {
+                    error!("ALERT,{:?},{},{}", account, balance, limit);
+                    true
+                } else {
+                    false
+                }
+            })
+            .count();
+        if depleted > 0 {
+            std::process::exit(1);
+        }
+        std::thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+fn read_watch_accounts(account: &Option<Address>, file: &Option<PathBuf>) -> anyhow::Result<Vec<Address>> {
+    let mut accounts = account.iter().copied().collect::<Vec<_>>();
+    if let Some(file) = file {
+        for line in std::fs::read_to_string(file)?.lines() {
+            let line = line.trim();
+            if !line.is_empty() {
+                accounts.push(Address::from_str(line)?);
+            }
+        }
+    }
+    if accounts.is_empty() {
+        anyhow::bail!("no account to watch, use --account or --accounts");
+    }
+    Ok(accounts)
+}
+
+/// check every account of a key file holds at least `expected` 0.1-eth
+fn eth_verify(
+    network: &str,
+    timeout: Option<u64>,
+    node_timeout: Option<u64>,
+    source: &Path,
+    expected: u64,
+) -> anyhow::Result<()> {
+    let network = real_network(network);
+    let client = TestClient::setup(network[0].clone(), timeout, node_timeout);
+    let expected = U256::exp10(17) * expected;
+    let addresses = load_keys(source)?
+        .iter()
+        .map(|key| Address::from_str(key.address.as_str()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let (mut insufficient, mut unknown) = (0usize, 0usize);
+    for (address, (balance, _)) in addresses.iter().zip(client.balances_and_nonces(&addresses)) {
+        match balance {
+            Some(balance) if balance >= expected => info!("{:?} pass, balance {}", address, balance),
+            Some(balance) => {
+                warn!("{:?} fail, balance {} is below {}", address, balance, expected);
+                insufficient += 1;
+            }
+            None => {
+                warn!("{:?} fail, balance is unknown", address);
+                unknown += 1;
+            }
+        }
+    }
+    let passed = addresses.len() - insufficient - unknown;
+    report::summary(format!(
+        "Verified: {}/{} passed, {:.1}%",
+        passed,
+        addresses.len(),
+        passed as f64 * 100.0 / addresses.len().max(1) as f64
+    ));
+    if insufficient > 0 {
+        Err(Error::InsufficientFunds(format!("{} accounts below {}", insufficient, expected)).into())
+    } else if unknown > 0 {
+        Err(Error::NodeUnreachable(format!("balances of {} accounts are unknown", unknown)).into())
+    } else {
+        Ok(())
+    }
+}
+
+fn eth_estimate_gas(
+    network: &str,
+    timeout: Option<u64>,
+    node_timeout: Option<u64>,
+    to: Address,
+    value: u128,
+    data: Option<&str>,
+    buffer_pct: u64,
+) -> anyhow::Result<()> {
+    let network = real_network(network);
+    let client = TestClient::setup(network[0].clone(), timeout, node_timeout);
+    let data = data
+        .map(|d| hex::decode(d.trim_start_matches("0x")))
+        .transpose()?
+        .map(Bytes::from);
+    let req = CallRequest {
+        from: Some(client.root_addr),
+        to: Some(to),
+        value: Some(U256::from(value)),
+        data,
+        ..Default::default()
+    };
+    match client.estimate_gas(req) {
+        Ok(gas) => {
+            let buffered = gas * (100 + buffer_pct) / 100;
+            info!("estimated gas {}, with {}% buffer {}", gas, buffer_pct, buffered);
+            Ok(())
+        }
+        Err(e) => match revert_data(&e).map(|d| describe_revert(&d)) {
+            Some(reason) => anyhow::bail!("gas estimation reverted: {}, {}", reason, e),
+            None => anyhow::bail!("gas estimation failed: {}", e),
+        },
+    }
+}
+
+/// blocks queried by one `eth_getLogs` request, halved when the node refuses a large result
+const LOGS_CHUNK: u64 = 1000;
+
+/// times a test transaction is resent after its nonce is rejected
+const NONCE_RESYNCS: usize = 3;
+
+/// pending nonces printed by a stall report, the other ones are printed at debug level
+const STALL_NONCES: usize = 10;
+
+fn is_too_many_results(e: &web3::Error) -> bool {
+    let msg = e.to_string().to_lowercase();
+    msg.contains("more than") || msg.contains("too many") || msg.contains("limit exceeded")
+}
+
+fn eth_logs(
+    network: &str,
+    timeout: Option<u64>,
+    node_timeout: Option<u64>,
+    address: Option<Address>,
+    topic: Option<H256>,
+    from: BlockTag,
+    to: BlockTag,
+    encoding: HashEncoding,
+) -> anyhow::Result<()> {
+    let network = real_network(network);
+    let client = TestClient::setup(network[0].clone(), timeout, node_timeout);
+    let (from, to) = (from.resolve(&client)?, to.resolve(&client)?);
+    let (mut start, mut chunk, mut total) = (from, LOGS_CHUNK, 0usize);
+    while start <= to {
+        let end = to.min(start + chunk - 1);
+        let mut filter = FilterBuilder::default()
+            .from_block(BlockNumber::Number(start.into()))
+            .to_block(BlockNumber::Number(end.into()));
+        if let Some(address) = address {
+            filter = filter.address(vec![address]);
+        }
+        if let Some(topic) = topic {
+            filter = filter.topics(Some(vec![topic]), None, None, None);
+        }
+        match client.logs(filter.build()) {
+            Ok(logs) => {
+                for log in logs.iter() {
+                    let topics = log.topics.iter().map(|t| format!("{:?}", t)).collect::<Vec<_>>();
+                    info!(
+                        "block,{},tx,{},index,{},address,{:?},topics,{},data,0x{}",
+                        log.block_number.unwrap_or_default(),
+                        encoding.encode(&log.transaction_hash.unwrap_or_default()),
+                        log.log_index.unwrap_or_default(),
+                        log.address,
+                        topics.join("|"),
+                        hex::encode(&log.data.0),
+                    );
+                }
+                total += logs.len();
+                start = end + 1;
+            }
+            Err(e) if chunk > 1 && is_too_many_results(&e) => {
+                chunk /= 2;
+                warn!(
+                    "too many logs in blocks {}-{}, narrowing to {} blocks: {}",
+                    start, end, chunk, e
+                );
+            }
+            Err(e) => anyhow::bail!("failed to get logs of blocks {}-{}: {}", start, end, e),
+        }
+    }
+    info!("{} logs found in blocks {}-{}", total, from, to);
+    Ok(())
 }
-fn eth_contract(network: &str, timeout: Option<u64>, optype: &ContractOP, config: &PathBuf) -> anyhow::Result<()> {
+
+fn eth_contract(
+    network: &str,
+    timeout: Option<u64>,
+    node_timeout: Option<u64>,
+    optype: &ContractOP,
+    config: &PathBuf,
+) -> anyhow::Result<()> {
     let network = real_network(network);
-    let client = TestClient::setup(network[0].clone(), timeout);
+    let client = TestClient::setup(network[0].clone(), timeout, node_timeout);
     match optype {
         ContractOP::Deploy => {
             let deploy_json = parse_deploy_json(config)?;
@@ -64,10 +349,116 @@ struct BlockInfo {
     timestamp: U256,
     count: usize,
     block_time: u64,
+    // reverted transactions and gas used, the inner none if the receipts could not be fetched
+    receipts: Option<Option<(usize, U256)>>,
+}
+
+/// columns of the block rows
+const BLOCK_HEADER: [&str; 4] = ["number", "timestamp", "txs", "block_time"];
+
+/// columns appended by `--with-receipts`
+const RECEIPT_HEADER: [&str; 2] = ["reverted", "gas_used"];
+
+/// reverted transactions and gas used of a block, from one `eth_getBlockReceipts` call if the node supports it,
+/// none if a receipt could not be fetched, partial counts would pass for real ones
+fn receipt_stats(client: &TestClient, block: &Block<H256>, block_receipts: bool) -> Option<(usize, U256)> {
+    let number = block.number.unwrap_or_default();
+    let receipts = if block_receipts {
+        match client.block_receipts(number) {
+            Ok(receipts) => receipts,
+            Err(e) => {
+                warn!("receipts of block {} unknown: {}", number, e);
+                return None;
+            }
+        }
+    } else {
+        let receipts = block
+            .transactions
+            .iter()
+            .filter_map(|h| client.transaction_receipt(*h))
+            .collect::<Vec<_>>();
+        if receipts.len() < block.transactions.len() {
+            warn!(
+                "receipts of block {} unknown: {} of {} fetched",
+                number,
+                receipts.len(),
+                block.transactions.len()
+            );
+            return None;
+        }
+        receipts
+    };
+    Some(receipt_totals(&receipts))
 }
 
-fn para_eth_blocks(client: TestClient, start: u64, end: u64) {
+/// reverted transactions and gas used of the receipts of a block
+fn receipt_totals(receipts: &[TransactionReceipt]) -> (usize, U256) {
+    let reverted = receipts.iter().filter(|r| r.status == Some(U64::zero())).count();
+    let gas_used = receipts
+        .iter()
+        .filter_map(|r| r.gas_used)
+        .fold(U256::zero(), |sum, gas| sum + gas);
+    (reverted, gas_used)
+}
+
+/// the receipts of `sample` from `eth_getBlockReceipts`, none if the node does not support it, the time of
+/// the call is logged against fetching the same receipts one transaction at a time
+fn probe_block_receipts(client: &TestClient, sample: &Block<H256>) -> Option<Vec<TransactionReceipt>> {
+    let number = sample.number.unwrap_or_default();
+    let begin = Instant::now();
+    let receipts = client.block_receipts(number).ok()?;
+    let batched = begin.elapsed();
+    if !sample.transactions.is_empty() {
+        let begin = Instant::now();
+        sample.transactions.iter().for_each(|h| {
+            client.transaction_receipt(*h);
+        });
+        info!(
+            "receipts of block {} with {} transactions: eth_getBlockReceipts {:?}, per transaction {:?}",
+            number,
+            sample.transactions.len(),
+            batched,
+            begin.elapsed()
+        );
+    }
+    Some(receipts)
+}
+
+/// the receipt columns of a block row, null if the receipts are unknown
+fn receipt_columns(receipts: Option<(usize, U256)>) -> [Value; 2] {
+    match receipts {
+        Some((reverted, gas_used)) => [json!(reverted), json!(gas_used.to_string())],
+        None => [Value::Null, Value::Null],
+    }
+}
+
+/// rows of the blocks from `start` to `end`, printed in height order or in the order of a column of `header`,
+/// the first `limit` ones if any
+fn para_eth_blocks(
+    client: TestClient,
+    start: u64,
+    end: u64,
+    with_receipts: bool,
+    only_with_txs: bool,
+    order: Option<(&[&str], &str, bool)>,
+    limit: Option<u64>,
+) -> anyhow::Result<Vec<Vec<Value>>> {
     let client = Arc::new(client);
+    // probed once on the first block, every block is then fetched with the method picked
+    let block_receipts = with_receipts
+        && client
+            .block_with_tx_hashes(BlockId::Number(BlockNumber::Number(U64::from(start))))
+            .and_then(|sample| probe_block_receipts(&client, &sample))
+            .is_some();
+    if with_receipts {
+        let method = if block_receipts {
+            "eth_getBlockReceipts"
+        } else {
+            "eth_getTransactionReceipt per transaction"
+        };
+        info!("fetching receipts with {}", method);
+    }
+    let begin = Instant::now();
     let pool = rayon::ThreadPoolBuilder::new().num_threads(8).build().unwrap();
     let (tx, rx) = mpsc::channel();
     (start..end).for_each(|n| {
@@ -80,6 +471,7 @@ fn para_eth_blocks(client: TestClient, start: u64, end: u64) {
                 timestamp: b.timestamp,
                 count: b.transactions.len(),
                 block_time: 0u64,
+                receipts: with_receipts.then(|| receipt_stats(&client, &b, block_receipts)),
             });
             tx.send((n, b)).unwrap();
         })
@@ -89,20 +481,146 @@ fn para_eth_blocks(client: TestClient, start: u64, end: u64) {
         let j = rx.recv().unwrap();
         *blocks.get_mut((j.0 - start) as usize).unwrap() = j.1
     }
-    blocks.iter().for_each(|b| {
-        if let Some(b) = b {
-            info!("{},{},{},{}", b.number, b.timestamp, b.count, b.block_time);
-        } else {
-            info!("None");
+    if with_receipts && end > start {
+        info!(
+            "{} blocks with receipts fetched in {:?}, {:?} per block",
+            end - start,
+            begin.elapsed(),
+            begin.elapsed() / (end - start) as u32
+        );
+    }
+    let mut empty = 0usize;
+    let mut rows = blocks
+        .iter()
+        .filter_map(|b| {
+            if let Some(b) = b {
+                if only_with_txs && b.count == 0 {
+                    empty += 1;
+                    return None;
+                }
+                let mut row = vec![
+                    json!(b.number),
+                    json!(b.timestamp.as_u64()),
+                    json!(b.count),
+                    json!(b.block_time),
+                ];
+                if let Some(receipts) = b.receipts {
+                    row.extend(receipt_columns(receipts));
+                }
+                Some(row)
+            } else {
+                info!("None");
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    if let Some((header, name, reverse)) = order {
+        output::sort_rows(header, &mut rows, name, reverse)?;
+    }
+    output::truncate_rows(&mut rows, limit);
+    rows.iter().for_each(|row| report::summary(output::line(row)));
+    if only_with_txs {
+        info!("{} blocks without transactions skipped", empty);
+    }
+    Ok(rows)
+}
+
+fn eth_follow_blocks(
+    network: &str,
+    timeout: Option<u64>,
+    node_timeout: Option<u64>,
+    only_with_txs: bool,
+) -> anyhow::Result<()> {
+    let network = real_network(network);
+    let url = network[0].clone().unwrap_or_default();
+    if url.starts_with("ws://") || url.starts_with("wss://") {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        loop {
+            match rt.block_on(follow_new_heads(url.as_str(), only_with_txs)) {
+                Ok(()) => warn!("subscription to {} closed, reconnecting", url),
+                Err(e) => error!("subscription to {} failed: {:?}, reconnecting", url, e),
+            }
+            std::thread::sleep(Duration::from_secs(3));
+        }
+    } else {
+        let client = TestClient::setup(network[0].clone(), timeout, node_timeout);
+        let mut last_height = client.block_number().unwrap_or_default();
+        let mut empty = 0u64;
+        loop {
+            match client.block_number() {
+                Some(current) if current > last_height => {
+                    for n in last_height.as_u64() + 1..=current.as_u64() {
+                        let id = BlockId::Number(BlockNumber::Number(U64::from(n)));
+                        if let Some(b) = client.block_with_tx_hashes(id) {
+                            if only_with_txs && b.transactions.is_empty() {
+                                empty += 1;
+                                continue;
+                            }
+                            if empty > 0 {
+                                info!("{} blocks without transactions skipped", std::mem::take(&mut empty));
+                            }
+                            info!("{},{:?},{}", n, b.timestamp, b.transactions.len());
+                        }
+                    }
+                    last_height = current;
+                }
+                Some(_) => {}
+                None => warn!("failed to obtain block height"),
+            }
+            std::thread::sleep(Duration::from_secs(1));
         }
-    })
+    }
+}
+
+/// print the blocks of the headers pushed by a websocket node until the subscription ends,
+/// headers carry no transactions so every block is fetched over the same connection for its count
+async fn follow_new_heads(url: &str, only_with_txs: bool) -> web3::Result<()> {
+    let transport = web3::transports::WebSocket::new(url).await?;
+    let web3 = web3::Web3::new(transport);
+    let mut heads = web3.eth_subscribe().subscribe_new_heads().await?;
+    let mut empty = 0u64;
+    while let Some(head) = heads.next().await {
+        let head = head?;
+        let id = match head.hash {
+            Some(hash) => BlockId::Hash(hash),
+            None => BlockId::Number(BlockNumber::Number(head.number.unwrap_or_default())),
+        };
+        let txs = match web3.eth().block(id).await? {
+            Some(b) => b.transactions.len(),
+            None => {
+                warn!("block {:?} of a pushed header not found", head.number);
+                continue;
+            }
+        };
+        if only_with_txs && txs == 0 {
+            empty += 1;
+            continue;
+        }
+        if empty > 0 {
+            info!("{} blocks without transactions skipped", std::mem::take(&mut empty));
+        }
+        info!("{},{:?},{}", head.number.unwrap_or_default(), head.timestamp, txs);
+    }
+    Ok(())
 }
 
-fn eth_blocks(network: &str, timeout: Option<u64>, start: Option<u64>, count: Option<i64>) {
+#[allow(clippy::too_many_arguments)]
+fn eth_blocks(
+    network: &str,
+    timeout: Option<u64>,
+    node_timeout: Option<u64>,
+    start: Option<BlockTag>,
+    count: Option<i64>,
+    with_receipts: bool,
+    only_with_txs: bool,
+    order: Option<(&[&str], &str, bool)>,
+    limit: Option<u64>,
+) -> anyhow::Result<Vec<Vec<Value>>> {
     let network = real_network(network);
     // use first endpoint to fund accounts
-    let client = TestClient::setup(network[0].clone(), timeout);
+    let client = TestClient::setup(network[0].clone(), timeout, node_timeout);
     if let Some(start) = start {
+        let start = start.resolve(&client).context("while resolving the start block")?;
         let range = count
             .map(|c| match c.cmp(&0i64) {
                 Ordering::Equal => start..start + 1,
@@ -116,24 +634,33 @@ fn eth_blocks(network: &str, timeout: Option<u64>, start: Option<u64>, count: Op
                 }
                 Ordering::Greater => start..start + c.unsigned_abs() as u64 + 1,
             })
-            .unwrap_or_else(|| match client.block_number() {
-                Some(end) if start > end.as_u64() => {
-                    panic!(
+            .map_or_else(
+                || match client.block_number() {
+                    Some(end) if start > end.as_u64() => Err(Error::NotSupport(format!(
                         "start block height is bigger than latest height({}>{})",
                         start,
                         end.as_u64()
-                    );
-                }
-                Some(end) => start..end.as_u64() + 1,
-                None => panic!("Failed to obtain block height"),
-            });
+                    ))),
+                    Some(end) => Ok(start..end.as_u64() + 1),
+                    None => Err(Error::NodeUnreachable("failed to obtain block height".to_owned())),
+                },
+                Ok,
+            )?;
         let _last_block: RefCell<Option<(u64, Block<H256>)>> = RefCell::new(if range.start == 0 {
             None
         } else {
             let id = BlockId::Number(BlockNumber::Number(U64::from(range.start - 1)));
-            Some((range.start - 1, client.block_with_tx_hashes(id).unwrap()))
+            client.block_with_tx_hashes(id).map(|b| (range.start - 1, b))
         });
-        para_eth_blocks(client, range.start, range.end);
+        para_eth_blocks(
+            client,
+            range.start,
+            range.end,
+            with_receipts,
+            only_with_txs,
+            order,
+            limit,
+        )
         //range
         //    .map(|number| {
         //        let id = BlockId::Number(BlockNumber::Number(U64::from(number)));
@@ -157,6 +684,13 @@ fn eth_blocks(network: &str, timeout: Option<u64>, start: Option<u64>, count: Op
         //        log::info!("{}", msg);
         //    });
     } else if let Some(b) = client.current_block() {
+        if only_with_txs && b.transactions.is_empty() {
+            info!(
+                "latest block {} has no transactions, skipped",
+                b.number.unwrap_or_default()
+            );
+            return Ok(vec![]);
+        }
         let block_time = match b.number {
             Some(n) if n > U64::zero() => {
                 if let Some(last) = client.block_with_tx_hashes(BlockId::Number(BlockNumber::Number(n.sub(1)))) {
@@ -167,70 +701,210 @@ fn eth_blocks(network: &str, timeout: Option<u64>, start: Option<u64>, count: Op
             }
             _ => None,
         };
-        log::info!(
+        report::summary(format!(
             "{},{:?},{},{}",
             b.number.unwrap_or_default(),
             b.timestamp,
             b.transactions.len(),
             block_time.unwrap_or_default(),
-        );
+        ));
+        let mut row = vec![
+            json!(b.number.unwrap_or_default().as_u64()),
+            json!(b.timestamp.as_u64()),
+            json!(b.transactions.len()),
+            json!(block_time.unwrap_or_default().as_u64()),
+        ];
+        if with_receipts {
+            let receipts = match probe_block_receipts(&client, &b) {
+                Some(receipts) => Some(receipt_totals(&receipts)),
+                None => receipt_stats(&client, &b, false),
+            };
+            if let Some((reverted, gas_used)) = receipts {
+                report::summary(format!("reverted {}, gas used {}", reverted, gas_used));
+            }
+            row.extend(receipt_columns(receipts));
+        }
+        Ok(vec![row])
     } else {
         error!("Cannot obtain current block");
+        Ok(vec![])
     }
 }
 
+/// TPS observed over the last `blocks` blocks, from their transaction counts and timestamps
+fn eth_chain_tps(network: &str, timeout: Option<u64>, node_timeout: Option<u64>, blocks: u64) -> anyhow::Result<()> {
+    let network = real_network(network);
+    let client = TestClient::setup(network[0].clone(), timeout, node_timeout);
+    let latest = client
+        .block_number()
+        .ok_or_else(|| anyhow::anyhow!("cannot obtain current block"))?
+        .as_u64();
+    // one more block for the time of the first one
+    let first = latest.saturating_sub(blocks);
+    let mut prev: Option<Block<H256>> = None;
+    let (mut start, mut txs, mut counted) = (U256::zero(), 0usize, 0u64);
+    info!("height,timestamp,txs,block_time,tps");
+    for n in first..=latest {
+        let id = BlockId::Number(BlockNumber::Number(U64::from(n)));
+        let block = client
+            .block_with_tx_hashes(id)
+            .ok_or_else(|| anyhow::anyhow!("cannot obtain block {}", n))?;
+        if let Some(p) = prev.as_ref() {
+            let block_time = block.timestamp.saturating_sub(p.timestamp).as_u64();
+            // blocks of the same second have no time of their own, their transactions count in the span
+            let tps = match block_time {
+                0 => "-".to_string(),
+                t => format!("{:.2}", block.transactions.len() as f64 / t as f64),
+            };
+            info!(
+                "{},{},{},{},{}",
+                n,
+                block.timestamp,
+                block.transactions.len(),
+                block_time,
+                tps
+            );
+            txs += block.transactions.len();
+            counted += 1;
+        } else {
+            start = block.timestamp;
+        }
+        prev = Some(block);
+    }
+    if counted == 0 {
+        anyhow::bail!("not enough blocks, the chain is at height {}", latest);
+    }
+    let span = prev
+        .map(|b| b.timestamp)
+        .unwrap_or_default()
+        .saturating_sub(start)
+        .as_u64();
+    if span == 0 {
+        warn!("the {} blocks share one timestamp, tps is unknown", counted);
+        report::summary(format!("{} txs in {} blocks, tps unknown", txs, counted));
+    } else {
+        report::summary(format!(
+            "{:.2} tps, {} txs in {} blocks over {}s",
+            txs as f64 / span as f64,
+            txs,
+            counted,
+            span
+        ));
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn fund_accounts(
     network: &str,
     timeout: Option<u64>,
-    block_time: u64,
+    node_timeout: Option<u64>,
     count: u64,
     am: u64,
     load: bool,
     redeposit: bool,
-) {
+    start_nonce: Option<u64>,
+    mnemonic: Option<&str>,
+    confirm: ConfirmOpts,
+    chunk_size: Option<u64>,
+    confirm_each: bool,
+    source_key: Option<(secp256k1::SecretKey, Address)>,
+    gas_limit: u64,
+    checkpoint: Option<&Path>,
+    index_base: u64,
+    shuffle: Option<u64>,
+    run_metrics: &Metrics,
+) -> anyhow::Result<()> {
+    check_gas_limit(gas_limit, &TxType::Legacy, &[])?;
+    let mut checkpoint = checkpoint.map(Checkpoint::open).transpose()?;
+    if confirm_each && chunk_size.is_some() {
+        anyhow::bail!("--confirm-each cannot be used with --chunk-size");
+    }
+    if shuffle.is_some() && chunk_size.is_some() {
+        anyhow::bail!("--shuffle cannot be used with --chunk-size");
+    }
     let mut amount = web3::types::U256::exp10(17); // 0.1 eth
     amount.mul_assign(am);
 
     let network = real_network(network);
     // use first endpoint to fund accounts
-    let client = TestClient::setup(network[0].clone(), timeout);
+    let mut client = TestClient::setup_with_root(network[0].clone(), timeout, node_timeout, source_key);
+    client.gas_limit = U256::from(gas_limit);
+    let node = network[0].clone().unwrap_or_default();
     let balance = client.balance(client.root_addr, None);
     info!("Balance of {:?}: {}", client.root_addr, balance);
+    let chain_id = client.chain_id().map(|id| id.as_u64());
+    if let Some(n) = start_nonce {
+        warn!(
+            "!!! overriding nonce of {:?} with {}, transactions may conflict with pending ones !!!",
+            client.root_addr, n
+        );
+    }
 
-    let mut source_keys = if load {
-        let keys: Vec<_> = serde_json::from_str(std::fs::read_to_string("source_keys.001").unwrap().as_str()).unwrap();
-        keys
+    // derived and generated keys are all funded, minus the ones the checkpoint funded already
+    let remaining = count.saturating_sub(checkpoint.as_ref().map_or(0, |c| c.funded() as u64));
+    if !load && amount.mul(remaining + 1) >= balance {
+        return Err(Error::InsufficientFunds(format!(
+            "balance {} of {:?} funds {} accounts at most",
+            balance,
+            client.root_addr,
+            balance / amount
+        ))
+        .into());
+    }
+
+    let mut source_keys = if let Some(phrase) = mnemonic {
+        debug!("deriving source keys from mnemonic");
+        derive_eth_keys(phrase, index_base, count)?
+    } else if load {
+        load_keys("source_keys.001")?
     } else {
-        // check if the key file exists
+        // an existing key file is only extended by a resumed chunked funding
         debug!("generating new source keys");
-        if std::fs::File::open("source_keys.001").is_ok() {
-            panic!("file \"source_keys.001\" already exists");
+        let resuming = checkpoint.is_some() && chunk_size.is_some();
+        if !resuming && Path::new("source_keys.001").exists() {
+            anyhow::bail!(
+                "source_keys.001 already exists, fund its keys with --load or resume with --chunk-size --resume-fund"
+            );
         }
-        if amount.mul(count + 1) >= balance {
-            panic!("Too large source account number, maximum {}", balance / amount);
+        if let Some(chunk_size) = chunk_size {
+            return fund_in_chunks(
+                &client,
+                &node,
+                count,
+                amount,
+                chunk_size,
+                &confirm,
+                start_nonce,
+                checkpoint.as_mut(),
+                index_base,
+                run_metrics,
+            );
         }
         let source_keys = (0..count).map(|_| one_eth_key()).collect::<Vec<_>>();
-        let data = serde_json::to_string(&source_keys).unwrap();
-        std::fs::write("source_keys.001", &data).unwrap();
+        write_keys("source_keys.001", chain_id, index_base, &source_keys)?;
 
         source_keys
     };
 
     // add more source keys and save them to file
-    if count as usize > source_keys.len() {
+    if mnemonic.is_none() && count as usize > source_keys.len() {
         source_keys.resize_with(count as usize, one_eth_key);
 
         std::fs::rename("source_keys.001", ".source_keys.001.bak").unwrap();
-        let data = serde_json::to_string(&source_keys).unwrap();
-        std::fs::write("source_keys.001", &data).unwrap();
+        write_keys("source_keys.001", chain_id, index_base, &source_keys)?;
     }
 
     let total = source_keys.len();
-    let source_accounts = source_keys
+    let mut source_accounts = source_keys
         .into_iter()
         .enumerate()
         .filter_map(|(idx, key)| {
             let from = Address::from_str(key.address.as_str()).unwrap();
+            if checkpoint.as_ref().map_or(false, |c| c.contains(&from)) {
+                debug!("{}/{} {:?} already funded", idx + 1, total, from);
+                return None;
+            }
             let account = if redeposit {
                 let balance = client.balance(from, None);
                 if balance < amount {
@@ -247,20 +921,697 @@ fn fund_accounts(
             account
         })
         .collect::<Vec<_>>();
+    // the root nonces follow the funding order, whatever it is
+    if let Some(seed) = shuffle {
+        template::shuffle(&mut source_accounts, seed);
+        info!("{} accounts shuffled, seed {}", source_accounts.len(), seed);
+    }
+    if confirm_each {
+        return fund_one_by_one(
+            &client,
+            &node,
+            &source_accounts,
+            &confirm,
+            start_nonce,
+            checkpoint.as_mut(),
+            run_metrics,
+        );
+    }
+    if let Some(checkpoint) = checkpoint.as_mut() {
+        let mut nonce = start_nonce.map(U256::from);
+        for batch in source_accounts.chunks(CHECKPOINT_INTERVAL) {
+            check_funding_aborted(run_metrics)?;
+            let metrics = client
+                .distribution(1, None, batch, &confirm, true, true, nonce.take())
+                .with_context(|| format!("while funding accounts from {:?} on node {}", client.root_addr, node))?;
+            record_fundings(run_metrics, &metrics);
+            checkpoint.record(&metrics)?;
+            info!("{} accounts funded and checkpointed", checkpoint.funded());
+        }
+        return Ok(());
+    }
     // 1000 eth
-    let _metrics = client
-        .distribution(1, None, &source_accounts, &Some(block_time), true, true)
-        .unwrap();
+    let metrics = client
+        .distribution(
+            1,
+            None,
+            &source_accounts,
+            &confirm,
+            true,
+            true,
+            start_nonce.map(U256::from),
+        )
+        .with_context(|| format!("while funding accounts from {:?} on node {}", client.root_addr, node))?;
+    record_fundings(run_metrics, &metrics);
+    check_funding_aborted(run_metrics)?;
     // save metrics to file
     //let data = serde_json::to_string(&metrics).unwrap();
     //std::fs::write("metrics.001", &data).unwrap();
+    report::summary(format!("Funded: {}/{}", metrics.succeed, metrics.total));
+    Ok(())
+}
+
+/// requests timed by [`compare_connection_reuse`] with each client
+const REUSE_SAMPLES: u32 = 20;
+
+/// log the mean latency of sequential requests to `url` with a connection pool of `pool_size`
+/// against a new connection for every request, leaves the pool size unset
+fn compare_connection_reuse(url: Option<String>, timeout: Option<u64>, node_timeout: Option<u64>, pool_size: usize) {
+    let mean = |size: usize| {
+        set_http_pool_size(size);
+        let client = TestClient::setup(url.clone(), timeout, node_timeout);
+        // the first request opens the connection a pool keeps
+        client.block_number()?;
+        let begin = Instant::now();
+        for _ in 0..REUSE_SAMPLES {
+            client.block_number()?;
+        }
+        Some(begin.elapsed() / REUSE_SAMPLES)
+    };
+    match (mean(pool_size), mean(0)) {
+        (Some(pooled), Some(fresh)) => info!(
+            "rpc latency with a pool of {} connections {:?}, with a new connection per request {:?}",
+            pool_size, pooled, fresh
+        ),
+        _ => warn!("node not answering, connection reuse not measured"),
+    }
+    set_http_pool_size(usize::MAX);
+}
+
+/// count the fundings of `transfers` in the metrics of the run, shown by the dashboard
+fn record_fundings(run_metrics: &Metrics, transfers: &TransferMetrics) {
+    transfers.txs.iter().for_each(|tx| {
+        let outcome = match tx.status {
+            1 => Outcome::Succeed,
+            0 => Outcome::Reverted,
+            _ => Outcome::Failed,
+        };
+        run_metrics.begin();
+        run_metrics.record(outcome, Duration::from_secs(tx.wait));
+    });
+}
+
+/// reject a gas limit too low for any transaction of the type carrying `data` to be accepted
+fn check_gas_limit(gas_limit: u64, tx_type: &TxType, data: &[u8]) -> anyhow::Result<()> {
+    let intrinsic = tx_type.intrinsic_gas(data);
+    if gas_limit < intrinsic {
+        anyhow::bail!(
+            "gas limit {} is below the intrinsic gas {} of {} transactions with {} bytes of data",
+            gas_limit,
+            intrinsic,
+            tx_type,
+            data.len()
+        );
+    }
+    Ok(())
+}
+
+/// send a test transaction at the pending nonce, resyncing it from the node when the node rejects it,
+/// returns the nonce last used and the hash if sent
+#[allow(clippy::too_many_arguments)]
+fn send_resyncing(
+    client: &TestClient,
+    (source, sender): (&secp256k1::SecretKey, Address),
+    target: &(Address, U256),
+    chain_id: u64,
+    gas_price: U256,
+    tx_type: &TxType,
+    data: &Bytes,
+    resyncs: &AtomicU64,
+    metrics: &Metrics,
+) -> (Option<U256>, Option<H256>) {
+    let mut nonce = client.pending_nonce(sender);
+    for attempt in 0..=NONCE_RESYNCS {
+        let used = match nonce {
+            Some(used) => used,
+            None => {
+                metrics.record_error(format!("nonce of {:?} unknown", sender));
+                break;
+            }
+        };
+        match client.distribution_simple(
+            source,
+            target,
+            Some(chain_id),
+            Some(gas_price),
+            Some(used),
+            tx_type,
+            data.clone(),
+        ) {
+            Ok(hash) => return (nonce, Some(hash)),
+            Err(e) if e.is_invalid_nonce() && attempt < NONCE_RESYNCS => {
+                // the node may still report the rejected nonce as pending, it is taken anyway
+                nonce = client.pending_nonce(sender).map(|pending| pending.max(used + 1));
+                resyncs.fetch_add(1, Relaxed);
+                warn!(
+                    "nonce {} of {:?} rejected, resynced to {:?}: {}",
+                    used, sender, nonce, e
+                );
+            }
+            Err(e) => {
+                metrics.record_error(format!("nonce {} of {:?}: {}", used, sender, e));
+                break;
+            }
+        }
+    }
+    (nonce, None)
+}
+
+/// replace a transaction past its deadline at the same nonce, nodes require a 10% higher gas price
+#[allow(clippy::too_many_arguments)]
+fn replace_expired(
+    client: &TestClient,
+    action: OnDeadline,
+    (source, sender): (&secp256k1::SecretKey, Address),
+    target: &(Address, U256),
+    chain_id: u64,
+    gas_price: U256,
+    nonce: U256,
+    tx_type: &TxType,
+    data: Bytes,
+) -> Option<H256> {
+    let (target, data) = match action {
+        OnDeadline::Fail => return None,
+        OnDeadline::Bump => (*target, data),
+        OnDeadline::Cancel => ((sender, U256::zero()), Bytes::default()),
+    };
+    let bumped = gas_price + gas_price / 8;
+    client
+        .distribution_simple(
+            source,
+            &target,
+            Some(chain_id),
+            Some(bumped),
+            Some(nonce),
+            tx_type,
+            data,
+        )
+        .map_err(|e| warn!("cannot replace transaction {} of {:?}: {:?}", nonce, sender, e))
+        .ok()
+}
+
+/// seed of a run without --seed
+fn new_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// print why a mined transaction reverted, replayed on the node
+fn warn_revert(client: &TestClient, hash: H256) {
+    match client.revert_reason(hash) {
+        Some(reason) => warn!("{:?} reverted: {}", hash, reason),
+        None => warn!("{:?} reverted, no revert data", hash),
+    }
+}
+
+/// pick the node of every account, round-robin over the list in key file order, or with `hashed` by
+/// rendezvous hashing: the listing with the highest hash of account, url and its occurrence wins, an account
+/// keeps its node whatever the order of the list and a new node only takes the accounts it wins,
+/// a node of weight N is listed N times and gets N times the accounts either way
+fn assign_nodes(
+    urls: &[String],
+    addresses: &[Address],
+    (timeout, node_timeout): (Option<u64>, Option<u64>),
+    gas_limit: U256,
+    hashed: bool,
+) -> HashMap<Address, Arc<TestClient>> {
+    // one client per distinct node, a weighted node is listed several times
+    let mut clients: HashMap<&str, Arc<TestClient>> = HashMap::new();
+    for url in urls {
+        clients.entry(url.as_str()).or_insert_with(|| {
+            let mut client = TestClient::setup(Some(url.clone()), timeout, node_timeout);
+            client.gas_limit = gas_limit;
+            Arc::new(client)
+        });
+    }
+    // the occurrence of every listing of a weighted node, so that each one hashes differently
+    let occurrences = urls
+        .iter()
+        .enumerate()
+        .map(|(idx, url)| urls[..idx].iter().filter(|u| *u == url).count() as u64)
+        .collect::<Vec<_>>();
+    let mut assigned: HashMap<&str, usize> = HashMap::new();
+    let pins = addresses
+        .iter()
+        .enumerate()
+        .map(|(idx, address)| {
+            let node = if hashed {
+                (0..urls.len())
+                    .max_by_key(|i| {
+                        let occurrence = occurrences[*i].to_be_bytes();
+                        web3::signing::keccak256(&[address.as_bytes(), urls[*i].as_bytes(), &occurrence].concat())
+                    })
+                    .unwrap_or_default()
+            } else {
+                idx % urls.len()
+            };
+            debug!("{:?} sent to {}", address, urls[node]);
+            *assigned.entry(urls[node].as_str()).or_default() += 1;
+            (*address, clients[urls[node].as_str()].clone())
+        })
+        .collect();
+    let mut counts = assigned.into_iter().collect::<Vec<_>>();
+    counts.sort();
+    counts
+        .into_iter()
+        .for_each(|(url, accounts)| info!("{} accounts sent to {}", accounts, url));
+    pins
+}
+
+/// send the transactions of a workload, the ones of an account in order at consecutive nonces,
+/// accounts in parallel on the test thread pool, on their pinned node if any
+#[allow(clippy::too_many_arguments)]
+fn run_workload(
+    client: &TestClient,
+    pins: Option<&HashMap<Address, Arc<TestClient>>>,
+    workload: &Workload,
+    pool: &[(secp256k1::SecretKey, Address)],
+    (chain_id, gas_price, tx_type): (u64, U256, &TxType),
+    receipt_wait: Option<ConfirmOpts>,
+    resyncs: &AtomicU64,
+    metrics: &Metrics,
+) {
+    workload.0.par_iter().for_each(|(idx, txs)| {
+        let (source, address) = &pool[*idx];
+        let client = pins.and_then(|pins| pins.get(address)).map_or(client, |c| c.as_ref());
+        let mut nonce = client.pending_nonce(*address);
+        for tx in txs {
+            if metrics.aborted() {
+                return;
+            }
+            metrics.begin();
+            let begin = Instant::now();
+            let gas = tx.gas.map_or(client.gas_limit, U256::from);
+            let sent = match nonce {
+                Some(used) => client
+                    .distribution_with_gas(
+                        source,
+                        &(tx.to, tx.value),
+                        gas,
+                        Some(chain_id),
+                        Some(gas_price),
+                        Some(used),
+                        tx_type,
+                        tx.data.clone(),
+                    )
+                    .map_err(|e| {
+                        if e.is_invalid_nonce() {
+                            // the next transaction of the account starts over from the node nonce
+                            nonce = client.pending_nonce(*address);
+                            resyncs.fetch_add(1, Relaxed);
+                        }
+                        metrics.record_error(format!("nonce {} of {:?}: {}", used, address, e));
+                        warn!("transaction {} of {:?} not sent: {}", used, address, e)
+                    })
+                    .ok(),
+                None => {
+                    warn!("nonce of {:?} unknown, transaction not sent", address);
+                    nonce = client.pending_nonce(*address);
+                    None
+                }
+            };
+            let latency = begin.elapsed();
+            let outcome = match (sent, receipt_wait.as_ref()) {
+                (Some(hash), Some(wait)) => match client.wait_for_receipt(hash, wait).and_then(|r| r.status) {
+                    Some(status) if status == U64::from(1u64) => Outcome::Succeed,
+                    Some(_) => {
+                        warn_revert(client, hash);
+                        Outcome::Reverted
+                    }
+                    None => Outcome::Failed,
+                },
+                (Some(_), None) => Outcome::Succeed,
+                (None, _) => Outcome::Failed,
+            };
+            if sent.is_some() {
+                nonce = nonce.map(|n| n + 1);
+            }
+            metrics.record(outcome, latency);
+        }
+    });
 }
 
-fn main() -> anyhow::Result<()> {
-    env_logger::init();
+/// top up a source account from the root key when its balance cannot pay the run, the root nonce is
+/// tracked locally as fundings of parallel workers would otherwise reuse it
+#[allow(clippy::too_many_arguments)]
+fn fund_just_in_time(
+    client: &TestClient,
+    address: Address,
+    cost: U256,
+    chain_id: u64,
+    gas_price: U256,
+    root_nonce: &Mutex<Option<U256>>,
+    confirm: &ConfirmOpts,
+    metrics: &Metrics,
+) {
+    let balance = client.balance(address, None);
+    if balance >= cost {
+        return;
+    }
+    let sent = {
+        let mut nonce = root_nonce.lock().unwrap();
+        let next = nonce.or_else(|| client.pending_nonce(client.root_addr));
+        let sent = next
+            .ok_or_else(|| format!("nonce of {:?} unknown", client.root_addr))
+            .and_then(|next| {
+                client
+                    .distribution_simple(
+                        &client.root_sk,
+                        &(address, cost - balance),
+                        Some(chain_id),
+                        Some(gas_price),
+                        Some(next),
+                        &TxType::Legacy,
+                        Bytes::default(),
+                    )
+                    .map(|hash| (hash, next))
+                    .map_err(|e| e.to_string())
+            });
+        // resync from the node after a failure, the nonce may not have been used
+        *nonce = sent.as_ref().ok().map(|(_, next)| *next + 1);
+        sent
+    };
+    let funded = match sent {
+        Ok((hash, _)) => match client.wait_for_receipt(hash, confirm).and_then(|r| r.status) {
+            Some(status) if status == U64::from(1u64) => true,
+            _ => {
+                warn!("funding {:?} of {:?} not confirmed", hash, address);
+                false
+            }
+        },
+        Err(e) => {
+            warn!("cannot fund {:?}: {}", address, e);
+            false
+        }
+    };
+    debug!("{:?} funded with {} wei: {}", address, cost - balance, funded);
+    metrics.record_funding(funded);
+}
+
+/// compare the chain id in the header of the source key file with the one of the node
+fn check_source_chain(path: &Path, source: Option<u64>, chain_id: u64, force: bool) -> anyhow::Result<()> {
+    match source {
+        None => warn!("{:?} records no chain id, the source keys cannot be validated", path),
+        Some(id) if id == chain_id => info!("source keys of {:?} match chain {}", path, chain_id),
+        Some(id) if force => warn!(
+            "!!! source keys of {:?} were generated for chain {}, the node is on chain {} !!!",
+            path, id, chain_id
+        ),
+        Some(id) => anyhow::bail!(
+            "source keys of {:?} were generated for chain {} but the node is on chain {}, --force to run anyway",
+            path,
+            id,
+            chain_id
+        ),
+    }
+    Ok(())
+}
+
+/// resolve `--source-key`, either a hex private key or `env:VARNAME`
+fn source_key(value: &str) -> anyhow::Result<(secp256k1::SecretKey, Address)> {
+    let secret = match value.strip_prefix("env:") {
+        Some(var) => {
+            std::env::var(var).map_err(|e| anyhow::anyhow!("cannot read source key from env {}: {}", var, e))?
+        }
+        None => {
+            warn!("!!! private key passed on the command line may leak through shell history and process lists, prefer --source-key env:VARNAME !!!");
+            value.to_owned()
+        }
+    };
+    parse_keypair(&secret).map_err(|e| anyhow::anyhow!("invalid source key: {}", e))
+}
+
+/// fund accounts strictly one after another for deterministic ordering
+fn fund_one_by_one(
+    client: &TestClient,
+    node: &str,
+    targets: &[(Address, U256)],
+    confirm: &ConfirmOpts,
+    start_nonce: Option<u64>,
+    mut checkpoint: Option<&mut Checkpoint>,
+    run_metrics: &Metrics,
+) -> anyhow::Result<()> {
+    let total = targets.len();
+    let mut succeed = 0u64;
+    for (idx, target) in targets.iter().enumerate() {
+        check_funding_aborted(run_metrics)?;
+        let begin = Instant::now();
+        let nonce = start_nonce.map(|n| U256::from(n + idx as u64));
+        let metrics = client
+            .distribution(1, None, std::slice::from_ref(target), confirm, true, false, nonce)
+            .with_context(|| {
+                format!(
+                    "while funding {:?} from {:?} on node {}",
+                    target.0, client.root_addr, node
+                )
+            })?;
+        succeed += metrics.succeed;
+        record_fundings(run_metrics, &metrics);
+        if let Some(checkpoint) = checkpoint.as_deref_mut() {
+            checkpoint.record(&metrics)?;
+        }
+        let tx = metrics.txs.first();
+        info!(
+            "{}/{} {:?} {:?} succeed {} latency {} ms",
+            idx + 1,
+            total,
+            target.0,
+            tx.and_then(|m| m.hash),
+            tx.map_or(false, |m| m.status == 1),
+            begin.elapsed().as_millis()
+        );
+    }
+    report::summary(format!("Funded one by one: {}/{}", succeed, total));
+    Ok(())
+}
+
+/// sample the chain TPS every block time and abort the funding through the metrics if it stays below
+/// `floor` for `window`
+fn watch_tps_floor(client: TestClient, metrics: Arc<Metrics>, floor: f64, window: Duration, interval: Duration) {
+    std::thread::spawn(move || {
+        let mut last = client.block_number().unwrap_or_default().as_u64();
+        let mut below_since = None;
+        loop {
+            let begin = Instant::now();
+            std::thread::sleep(interval);
+            let current = client.block_number().map_or(last, |h| h.as_u64());
+            // a stalled chain produces no new blocks, which counts as zero TPS
+            let txs = (last + 1..=current)
+                .filter_map(|h| client.block_with_tx_hashes_inner(BlockId::Number(h.into()), None, Some(3)))
+                .map(|b| b.transactions.len())
+                .sum::<usize>();
+            last = current;
+            let tps = txs as f64 / begin.elapsed().as_secs_f64();
+            debug!("chain TPS {:.3}, height {}", tps, current);
+            if tps >= floor {
+                below_since = None;
+                continue;
+            }
+            let since = *below_since.get_or_insert(begin);
+            if since.elapsed() >= window {
+                error!(
+                    "chain TPS {:.3} stayed below the floor {} for {} seconds, aborting funding",
+                    tps,
+                    floor,
+                    since.elapsed().as_secs()
+                );
+                metrics.abort();
+                return;
+            }
+        }
+    });
+}
+
+/// fail a funding the tps floor watcher aborted, the accounts funded so far are kept
+fn check_funding_aborted(run_metrics: &Metrics) -> anyhow::Result<()> {
+    if run_metrics.aborted() {
+        anyhow::bail!(
+            "funding aborted after {} accounts, the chain TPS stayed below --min-tps-floor",
+            run_metrics.succeed()
+        );
+    }
+    Ok(())
+}
+
+/// check every second that a test transaction completed within `window` while some remain, a stalled
+/// run prints what it waits for, then aborts through the metrics or keeps going
+fn watch_stalls(
+    client: Arc<TestClient>,
+    metrics: Arc<Metrics>,
+    addresses: Vec<Address>,
+    total: u64,
+    window: Duration,
+    on_stall: OnStall,
+) {
+    std::thread::spawn(move || {
+        let mut reported: Option<Instant> = None;
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let snapshot = metrics.snapshot();
+            if snapshot.sent + snapshot.duplicates >= total {
+                return;
+            }
+            let idle = metrics.idle();
+            if idle < window || reported.map_or(false, |r| r.elapsed() < window) {
+                continue;
+            }
+            reported = Some(Instant::now());
+            error!(
+                "!!! no transaction completed for {}s, {}/{} done !!!",
+                idle.as_secs(),
+                snapshot.sent,
+                total
+            );
+            error!("in flight: {}", metrics.inflight());
+            error!(
+                "last error: {}",
+                metrics.last_error().unwrap_or_else(|| "none".to_owned())
+            );
+            let begin = Instant::now();
+            match client.block_number() {
+                Some(height) => error!(
+                    "node: height {}, answered in {:?}, {} pending transactions",
+                    height,
+                    begin.elapsed(),
+                    client.pending_tx_count().unwrap_or_default()
+                ),
+                None => error!("node: not answering, gave up after {:?}", begin.elapsed()),
+            }
+            let nonces = client.balances_and_nonces(&addresses);
+            addresses
+                .iter()
+                .zip(nonces)
+                .enumerate()
+                .for_each(|(idx, (address, (_, nonce)))| {
+                    let nonce = nonce.map_or_else(|| "unknown".to_owned(), |n| n.to_string());
+                    if idx < STALL_NONCES {
+                        error!("pending nonce of {:?}: {}", address, nonce);
+                    } else {
+                        debug!("pending nonce of {:?}: {}", address, nonce);
+                    }
+                });
+            if addresses.len() > STALL_NONCES {
+                error!("{} more pending nonces at debug level", addresses.len() - STALL_NONCES);
+            }
+            if on_stall == OnStall::Abort {
+                metrics.abort();
+                return;
+            }
+            warn!(
+                "stalled run continues, the next report comes after another {:?}",
+                window
+            );
+        }
+    });
+}
+
+/// generate and fund new accounts chunk by chunk, appending every funded chunk to the key file,
+/// accounts of the checkpoint count as funded already
+#[allow(clippy::too_many_arguments)]
+fn fund_in_chunks(
+    client: &TestClient,
+    node: &str,
+    count: u64,
+    amount: U256,
+    chunk_size: u64,
+    confirm: &ConfirmOpts,
+    start_nonce: Option<u64>,
+    mut checkpoint: Option<&mut Checkpoint>,
+    index_base: u64,
+    run_metrics: &Metrics,
+) -> anyhow::Result<()> {
+    if chunk_size == 0 {
+        anyhow::bail!("chunk size should be larger than zero");
+    }
+    let mut nonce = start_nonce.map(U256::from);
+    let mut funded = checkpoint.as_ref().map_or(0, |c| c.funded() as u64);
+    let chain_id = client.chain_id().map(|id| id.as_u64());
+    // keys saved by an interrupted run but not funded, funded before new ones are generated
+    let mut unfunded = match checkpoint.as_deref() {
+        Some(checkpoint) if Path::new("source_keys.001").exists() => load_keys("source_keys.001")?
+            .into_iter()
+            .filter(|key| !checkpoint.contains(&Address::from_str(key.address.as_str()).unwrap()))
+            .collect::<Vec<_>>(),
+        _ => vec![],
+    };
+    if !unfunded.is_empty() {
+        info!("{} saved keys are not funded yet, funding them first", unfunded.len());
+    }
+    while funded < count {
+        check_funding_aborted(run_metrics)?;
+        let size = chunk_size.min(count - funded) as usize;
+        let mut keys = unfunded.drain(..size.min(unfunded.len())).collect::<Vec<_>>();
+        let fresh = (keys.len()..size).map(|_| one_eth_key()).collect::<Vec<_>>();
+        // saved before any fund leaves the root account, a failed chunk leaves unfunded keys, never lost ones
+        append_keys("source_keys.001", chain_id, index_base, &fresh)?;
+        keys.extend(fresh);
+        let targets = keys
+            .iter()
+            .map(|key| (Address::from_str(key.address.as_str()).unwrap(), amount))
+            .collect::<Vec<_>>();
+        let metrics = client
+            .distribution(1, None, &targets, confirm, true, true, nonce.take())
+            .with_context(|| {
+                format!(
+                    "while funding accounts from {:?} on node {}, the keys of the chunk are in source_keys.001, \
+                     fund them with --resume-fund or --load --redeposit",
+                    client.root_addr, node
+                )
+            })?;
+        record_fundings(run_metrics, &metrics);
+        if let Some(checkpoint) = checkpoint.as_deref_mut() {
+            checkpoint.record(&metrics)?;
+        }
+        funded += keys.len() as u64;
+        info!("{}/{} accounts funded", funded, count);
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {:?}", e);
+        let code = e
+            .chain()
+            .find_map(|e| e.downcast_ref::<Error>())
+            .map_or(1, Error::exit_code);
+        std::process::exit(code);
+    }
+}
 
-    let cli = Cli::parse_args();
+fn run() -> anyhow::Result<()> {
+    let mut cli = Cli::parse_args();
+    // log lines would scroll over the dashboard, it shows them in a panel instead
+    let logs = cli.tui().then(LogTail::default);
+    if cli.quiet && logs.is_some() {
+        anyhow::bail!("--quiet cannot be used with --tui");
+    }
+    report::set_quiet(cli.quiet);
+    match &logs {
+        Some(logs) => env_logger::Builder::from_default_env()
+            .target(env_logger::Target::Pipe(Box::new(logs.clone())))
+            .init(),
+        None => {
+            color::init(cli.no_color);
+            let mut builder = env_logger::Builder::from_default_env();
+            if cli.quiet {
+                // errors still reach stderr, progress lines are dropped
+                builder.filter_level(log::LevelFilter::Error);
+            }
+            builder.format(color::format).init()
+        }
+    }
+    cli.merge_network_file()?;
+    cli.check_transport()?;
     debug!("{:?}", cli);
+    // reported when main returns, whichever way it does
+    let _rate_limits = RateLimitReport;
+    set_rpc_overrides(&cli.rpc_override).map_err(anyhow::Error::msg)?;
+    set_backoff_jitter(cli.backoff_jitter);
+    if let Some(size) = cli.http_pool_size {
+        set_http_pool_size(size as usize);
+    }
     info!("logical cpus {}, physical cpus {}", log_cpus(), phy_cpus());
 
     match &cli.command {
@@ -272,50 +1623,465 @@ fn main() -> anyhow::Result<()> {
             amount,
             load,
             redeposit,
+            start_nonce,
+            mnemonic,
+            poll_interval,
+            confirm_timeout,
+            chunk_size,
+            min_tps_floor,
+            tps_window,
+            confirm_each,
+            source_key: key,
+            tui: _,
+            gas_limit,
+            resume_fund,
+            account_index_base,
+            shuffle,
+            seed,
         }) => {
+            let key = key.as_deref().map(source_key).transpose()?;
+            let metrics = Arc::new(Metrics::default());
+            let _dashboard = logs
+                .map(|logs| {
+                    let nodes = real_network(network.get_url().as_str()).into_iter().flatten().collect();
+                    Dashboard::start(nodes, (*timeout, cli.node_timeout), key, Some(metrics.clone()), logs)
+                })
+                .transpose()?;
+            if let Some(floor) = min_tps_floor {
+                let client = TestClient::setup_with_root(Some(network.get_url()), *timeout, cli.node_timeout, key);
+                watch_tps_floor(
+                    client,
+                    metrics.clone(),
+                    *floor,
+                    Duration::from_secs(*tps_window),
+                    Duration::from_secs(*block_time),
+                );
+            }
             fund_accounts(
                 network.get_url().as_str(),
                 *timeout,
-                *block_time,
+                cli.node_timeout,
                 *count,
                 *amount,
                 *load,
                 *redeposit,
+                *start_nonce,
+                mnemonic.as_deref(),
+                ConfirmOpts::new(*block_time, *poll_interval, *confirm_timeout),
+                *chunk_size,
+                *confirm_each,
+                key,
+                *gas_limit,
+                resume_fund.as_deref(),
+                *account_index_base,
+                shuffle.then(|| seed.unwrap_or_else(new_seed)),
+                &metrics,
+            )
+        }
+        Some(Commands::Keys {
+            mnemonic,
+            count,
+            to_file,
+            account_index_base,
+            force,
+        }) => {
+            if to_file.exists() && !*force {
+                anyhow::bail!("{:?} already exists, --force to overwrite it", to_file);
+            }
+            let keys = derive_eth_keys(mnemonic, *account_index_base, *count)?;
+            // derived keys work on any chain
+            write_keys(to_file, None, *account_index_base, &keys)?;
+            info!(
+                "{} keys m/44'/60'/0'/0/{}..{} derived and saved to {:?}",
+                keys.len(),
+                account_index_base,
+                account_index_base + count,
+                to_file
             );
             Ok(())
         }
+        Some(Commands::Addresses {
+            source,
+            mnemonic,
+            count,
+            account_index_base,
+            keystore,
+            keystore_password,
+        }) => {
+            let keys = match (mnemonic, keystore) {
+                (Some(_), Some(_)) => anyhow::bail!("--mnemonic and --keystore are exclusive"),
+                (Some(phrase), None) => derive_eth_keys(phrase, *account_index_base, *count)?,
+                (None, Some(keystore)) => {
+                    let password = keystore_password
+                        .as_deref()
+                        .ok_or_else(|| anyhow::anyhow!("--keystore needs --keystore-password"))?;
+                    load_keystore(keystore, password)?
+                }
+                (None, None) => load_keys(source)?,
+            };
+            keys.iter().for_each(|k| println!("{}", k.address));
+            Ok(())
+        }
+        Some(Commands::Verify {
+            network,
+            timeout,
+            source,
+            expected_amount,
+        }) => eth_verify(
+            network.get_url().as_str(),
+            *timeout,
+            cli.node_timeout,
+            source,
+            *expected_amount,
+        ),
         Some(Commands::Info {
             network,
             timeout,
             account,
+            decimals,
+            group_digits,
+            output_file,
+            output,
         }) => {
-            eth_account(network.get_url().as_str(), *timeout, *account);
+            let out = Output::from_args(output_file, *output)?;
+            let row = eth_account(
+                network.get_url().as_str(),
+                *timeout,
+                cli.node_timeout,
+                *account,
+                *decimals,
+                *group_digits,
+            );
+            if let Some(out) = out {
+                out.write(&["account", "balance", "nonce"], &[row])?;
+            }
             Ok(())
         }
-        Some(Commands::Transaction { network, timeout, hash }) => {
-            eth_transaction(network.get_url().as_str(), *timeout, *hash);
+        Some(Commands::Watch {
+            network,
+            timeout,
+            account,
+            accounts,
+            threshold,
+            interval,
+        }) => {
+            let accounts = read_watch_accounts(account, accounts)?;
+            eth_watch(
+                network.get_url().as_str(),
+                *timeout,
+                cli.node_timeout,
+                &accounts,
+                *threshold,
+                *interval,
+            );
             Ok(())
         }
+        Some(Commands::Logs {
+            network,
+            timeout,
+            address,
+            topic,
+            from,
+            to,
+        }) => eth_logs(
+            network.get_url().as_str(),
+            *timeout,
+            cli.node_timeout,
+            *address,
+            *topic,
+            *from,
+            *to,
+            cli.hash_encoding,
+        ),
+        Some(Commands::EstimateGas {
+            network,
+            timeout,
+            to,
+            value,
+            data,
+            buffer_pct,
+        }) => eth_estimate_gas(
+            network.get_url().as_str(),
+            *timeout,
+            cli.node_timeout,
+            *to,
+            *value,
+            data.as_deref(),
+            *buffer_pct,
+        ),
+        Some(Commands::Transaction {
+            network,
+            timeout,
+            hash,
+            abi,
+        }) => eth_transaction(
+            network.get_url().as_str(),
+            *timeout,
+            cli.node_timeout,
+            *hash,
+            cli.hash_encoding,
+            abi.as_deref(),
+        ),
+        Some(Commands::ChainTps {
+            network,
+            timeout,
+            blocks,
+        }) => eth_chain_tps(network.get_url().as_str(), *timeout, cli.node_timeout, *blocks),
         Some(Commands::Block {
             network,
             timeout,
             start,
             count,
+            follow,
+            output_file,
+            output,
+            with_receipts,
+            metric_rotate,
+            only_heights_with_txs,
+            sort_by,
+            reverse,
+            limit,
+        }) => {
+            if *follow && output_file.is_some() {
+                anyhow::bail!("--output-file cannot be used with --follow, the followed blocks are only printed");
+            }
+            let out = Output::from_args(output_file, *output)?;
+            if metric_rotate.is_some() && (out.is_none() || *follow) {
+                anyhow::bail!("--metric-rotate needs --output-file and cannot be used with --follow");
+            }
+            // height order unless sorted by another column
+            let order = (sort_by.is_some() || *reverse).then(|| (sort_by.as_deref().unwrap_or("number"), *reverse));
+            if (order.is_some() || limit.is_some()) && (*follow || metric_rotate.is_some()) {
+                anyhow::bail!("--sort-by, --reverse and --limit cannot be used with --follow or --metric-rotate");
+            }
+            let mut header = BLOCK_HEADER.to_vec();
+            if *with_receipts {
+                header.extend(RECEIPT_HEADER);
+            }
+            if let Some((name, _)) = order {
+                output::column(&header, name)?;
+            }
+            if *follow {
+                eth_follow_blocks(
+                    network.get_url().as_str(),
+                    *timeout,
+                    cli.node_timeout,
+                    *only_heights_with_txs,
+                )
+            } else {
+                let rows = eth_blocks(
+                    network.get_url().as_str(),
+                    *timeout,
+                    cli.node_timeout,
+                    *start,
+                    *count,
+                    *with_receipts,
+                    *only_heights_with_txs,
+                    order.map(|(name, reverse)| (&header[..], name, reverse)),
+                    *limit,
+                )?;
+                if let Some(out) = out {
+                    match metric_rotate {
+                        Some(rotate) => out.write_rotated(&header, &rows, *rotate, |row| {
+                            let (height, timestamp) = (row[0].as_u64(), row[1].as_u64());
+                            (height.unwrap_or_default(), timestamp.unwrap_or_default())
+                        })?,
+                        None => out.write(&header, &rows)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+        Some(Commands::Matrix {
+            config,
+            out_dir,
+            output_file,
+            output,
         }) => {
-            eth_blocks(network.get_url().as_str(), *timeout, *start, *count);
+            let out = Output::from_args(output_file, *output)?;
+            let config = matrix::MatrixConfig::load(config)?;
+            let runs = matrix::run(&config, out_dir)?;
+            let names = config.names();
+            matrix::print(&names, &runs);
+            if let Some(out) = out {
+                let header = names.iter().copied().chain(matrix::METRIC_HEADER).collect::<Vec<_>>();
+                out.write(&header, &matrix::rows(&runs))?;
+            }
+            let failed = runs.iter().filter(|run| run.metrics.is_none()).count();
+            if failed > 0 {
+                anyhow::bail!("{} of {} matrix runs wrote no summary", failed, runs.len());
+            }
+            Ok(())
+        }
+        Some(Commands::Cancel {
+            network,
+            timeout,
+            account_key,
+            nonce,
+            gas_price,
+            confirm_timeout,
+        }) => {
+            let (secret, address) = source_key(account_key)?;
+            let client = TestClient::setup(Some(network.get_url()), *timeout, cli.node_timeout);
+            let chain_id = client
+                .chain_id()
+                .ok_or_else(|| Error::NodeUnreachable("cannot get the chain id of the node".to_owned()))?
+                .as_u64();
+            let nonce = match nonce {
+                Some(nonce) => U256::from(*nonce),
+                None => client
+                    .nonce(address, Some(BlockNumber::Latest))
+                    .ok_or_else(|| Error::NodeUnreachable(format!("cannot get the nonce of {:?}", address)))?,
+            };
+            let pending = client.pending_nonce(address).unwrap_or_default();
+            if pending < nonce {
+                return Err(Error::NonceGap(format!(
+                    "the next nonce of {:?} is {}, a cancellation at {} would never replace anything",
+                    address, pending, nonce
+                ))
+                .into());
+            } else if pending == nonce {
+                warn!(
+                    "no pending transaction of {:?} at nonce {}, the cancellation will be mined as a plain transfer",
+                    address, nonce
+                );
+            }
+            let gas_price = match gas_price {
+                Some(price) => U256::from(*price),
+                None => client
+                    .gas_price()
+                    .ok_or_else(|| Error::NodeUnreachable("cannot get the gas price of the node".to_owned()))?,
+            };
+            let hash = replace_expired(
+                &client,
+                OnDeadline::Cancel,
+                (&secret, address),
+                &(address, U256::zero()),
+                chain_id,
+                gas_price,
+                nonce,
+                &TxType::Legacy,
+                Bytes::default(),
+            )
+            .ok_or_else(|| anyhow::anyhow!("cancellation of nonce {} of {:?} was not accepted", nonce, address))?;
+            let encoded = cli.hash_encoding.encode(&hash);
+            info!("cancellation {} sent for nonce {} of {:?}", encoded, nonce, address);
+            match client.wait_for_receipt(hash, &ConfirmOpts::new(BLOCK_TIME, 1000, *confirm_timeout)) {
+                Some(receipt) => {
+                    report::summary(format!(
+                        "Cancelled nonce {} of {:?} with {} in block {:?}",
+                        nonce, address, encoded, receipt.block_number
+                    ));
+                    Ok(())
+                }
+                None => anyhow::bail!(
+                    "cancellation {} was not mined in time, nonce {} may still be stuck",
+                    encoded,
+                    nonce
+                ),
+            }
+        }
+        Some(Commands::Decode { raw }) => {
+            let raw = hex::decode(raw.trim().trim_start_matches("0x"))?;
+            let tx = decode_raw_tx(&raw)?;
+            let mut value = tx.to_json();
+            value["hash"] = json!(cli.hash_encoding.encode(&tx.hash));
+            info!("{}", serde_json::to_string_pretty(&value)?);
             Ok(())
         }
         Some(Commands::Etl {
             abcid,
             tendermint,
             redis,
+            redis_port,
+            redis_db,
             load,
+            approx_quantiles,
+            store,
+            store_codec,
+            on_duplicate,
+            heights_file,
+            min_height,
+            max_height,
+            parse_only,
+            from_rpc,
+            strict_timestamps,
+            verify_hashes,
+            flush_interval,
+            tps_window,
+            max_log_line_bytes,
+            gap_report,
+            check_store,
+            cross_check_rpc,
+            store_rate_limit,
+            metric_fields,
         }) => {
-            let _ = Cli::etl_cmd(abcid, tendermint, redis.as_str(), *load);
+            Cli::etl_cmd(
+                abcid,
+                tendermint,
+                redis.as_str(),
+                *redis_port,
+                *redis_db,
+                *load,
+                *approx_quantiles,
+                store,
+                *store_codec,
+                *on_duplicate,
+                heights_file,
+                *min_height,
+                *max_height,
+                *parse_only,
+                from_rpc.as_ref().map(Network::get_url),
+                cli.node_timeout,
+                *strict_timestamps,
+                *verify_hashes,
+                *flush_interval,
+                *tps_window,
+                *max_log_line_bytes,
+                gap_report,
+                *check_store,
+                cross_check_rpc.as_ref().map(Network::get_url),
+                *store_rate_limit,
+                metric_fields.as_ref(),
+            )?;
+            Ok(())
+        }
+        Some(Commands::Summary {
+            redis,
+            redis_port,
+            redis_db,
+            start,
+            end,
+            approx_quantiles,
+            exclude_zero_tx,
+            output_file,
+            output,
+            sort_by,
+            reverse,
+            limit,
+            metric_fields,
+        }) => {
+            let out = Output::from_args(output_file, *output)?;
+            // height order unless sorted by another column
+            let order = (sort_by.is_some() || *reverse || limit.is_some() || metric_fields.is_some())
+                .then(|| (sort_by.as_deref().unwrap_or("height"), *reverse));
+            Cli::summary_cmd(
+                redis.as_str(),
+                *redis_port,
+                *redis_db,
+                *start,
+                *end,
+                *approx_quantiles,
+                *exclude_zero_tx,
+                out,
+                order,
+                *limit,
+                metric_fields.as_ref(),
+            )?;
             Ok(())
         }
         Some(Commands::Profiler { network, enable }) => {
-            let _ = Cli::profiler(network.as_str(), *enable);
+            Cli::profiler(network.as_str(), *enable)?;
             Ok(())
         }
         Some(Commands::Contract {
@@ -325,12 +2091,13 @@ fn main() -> anyhow::Result<()> {
             timeout,
         }) => {
             let rpc_url = network.get_url();
-            eth_contract(&rpc_url, *timeout, optype, config)?;
+            eth_contract(&rpc_url, *timeout, cli.node_timeout, optype, config)?;
             Ok(())
         }
         Some(Commands::Test {
             network,
-            mode: _,
+            mode,
+            recipient_pattern,
             delay,
             max_threads,
             count,
@@ -339,17 +2106,122 @@ fn main() -> anyhow::Result<()> {
             timeout,
             need_retry,
             check_balance,
+            require_receipt_status,
+            poll_interval,
+            confirm_timeout,
+            tx_type,
+            access_list,
+            adaptive,
+            target_latency,
+            adaptive_step,
+            sign_bench,
+            summary_json,
+            metric_flush_interval,
+            asserts,
+            backpressure_threshold,
+            tui: _,
+            gas_limit,
+            baseline: baseline_file,
+            regression_pct,
+            data_template,
+            to,
+            seed,
+            shuffle,
+            precheck,
+            tx_deadline,
+            on_deadline,
+            stall_window,
+            on_stall,
+            max_gas_spend,
+            transactions_from_file,
+            jit_fund,
+            validate_source,
+            force,
+            pin_node_per_account,
         }) => {
+            if metric_flush_interval.is_some() && summary_json.is_none() {
+                anyhow::bail!("--metric-flush-interval needs --summary-json");
+            }
+            if *metric_flush_interval == Some(0) {
+                anyhow::bail!("--metric-flush-interval needs at least one second");
+            }
+            if *stall_window == Some(0) {
+                anyhow::bail!("--stall-window needs at least one second");
+            }
+            if max_gas_spend.is_some() && !*require_receipt_status && tx_deadline.is_none() {
+                anyhow::bail!(
+                    "--max-gas-spend adds up the gas of receipts, it needs --require-receipt-status or --tx-deadline"
+                );
+            }
+            if max_gas_spend.is_some() && transactions_from_file.is_some() {
+                anyhow::bail!("--max-gas-spend limits the rounds, it cannot be used with --transactions-from-file");
+            }
+            if *jit_fund && (*check_balance || *precheck) {
+                anyhow::bail!(
+                    "--jit-fund funds the source accounts, it cannot be used with --check-balance or --precheck"
+                );
+            }
             let max_par = *max_threads;
             let source_file = source;
-            let _block_time = Some(*block_time);
+            let confirm = ConfirmOpts::new(*block_time, *poll_interval, *confirm_timeout);
+            let mut tx_type = tx_type.clone();
+            match (&mut tx_type, access_list) {
+                (TxType::AccessList(list), Some(file)) => {
+                    *list = serde_json::from_str(std::fs::read_to_string(file)?.as_str())?;
+                }
+                (_, Some(_)) => anyhow::bail!("--access-list needs --tx-type 2930, not {}", tx_type),
+                _ => {}
+            }
+            let baseline = baseline_file.as_deref().map(baseline::load).transpose()?;
             let timeout = Some(*timeout);
             let count = *count;
             let _need_retry = *need_retry;
 
-            let source_keys: Vec<KeyPair> =
-                serde_json::from_str(std::fs::read_to_string(source_file).unwrap().as_str()).unwrap();
-            let target_amount = web3::types::U256::exp10(16); // 0.01 eth
+            let (source_keys, source_chain_id) = load_keys_with_chain(source_file)?;
+            let workload = transactions_from_file
+                .as_deref()
+                .map(|path| Workload::load(path, source_keys.len()))
+                .transpose()?;
+            // workload indices refer to the key file, whatever the checks below exclude
+            let pool = source_keys
+                .iter()
+                .enumerate()
+                .map(|(idx, kp)| {
+                    let invalid = |e: String| Error::Codec(format!("key {} of {:?}: {}", idx, source_file, e));
+                    Ok((
+                        secp256k1::SecretKey::from_str(kp.private.as_str()).map_err(|e| invalid(e.to_string()))?,
+                        Address::from_str(kp.address.as_str()).map_err(|e| invalid(e.to_string()))?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            let self_tx = *mode == TestMode::SelfTx;
+            if to.is_some() && data_template.is_none() {
+                anyhow::bail!("--to calls a contract with the calldata of --data-template, which is missing");
+            }
+            if to.is_some() && (self_tx || recipient_pattern.is_some()) {
+                anyhow::bail!("--to sends to a contract, it cannot be used with the self mode or --recipient-pattern");
+            }
+            let recipients = match recipient_pattern {
+                Some(pattern) if self_tx && *pattern != RecipientPattern::SelfTx => {
+                    anyhow::bail!(
+                        "the self mode sends to the source accounts, it cannot use --recipient-pattern {}",
+                        pattern
+                    )
+                }
+                Some(pattern) => *pattern,
+                None if self_tx => RecipientPattern::SelfTx,
+                None => match to {
+                    Some(contract) => RecipientPattern::Fixed(*contract),
+                    None => RecipientPattern::Random,
+                },
+            };
+            info!("recipients:   {}", recipients);
+            // a contract call carries no value, the contract may not be payable
+            let target_amount = if self_tx || to.is_some() {
+                U256::zero()
+            } else {
+                web3::types::U256::exp10(16) // 0.01 eth
+            };
 
             check_parallel_args(max_par);
 
@@ -360,36 +2232,97 @@ fn main() -> anyhow::Result<()> {
                 .unwrap();
             info!("thread pool size {}", max_pool_size);
 
-            let url = network.get_url();
-            let client = Arc::new(TestClient::setup(Some(url), timeout));
+            // the first node of a list answers the queries, the accounts are spread over all of them
+            let urls = real_network(network.get_url().as_str())
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            let url = urls.first().cloned().or_else(|| Some(network.get_url()));
+            // every sending thread keeps its connection to the node between requests
+            let http_pool_size = cli.http_pool_size.map_or(max_pool_size, |size| size as usize);
+            compare_connection_reuse(url.clone(), timeout, cli.node_timeout, http_pool_size);
+            set_http_pool_size(http_pool_size);
+            info!("http pool size {}", http_pool_size);
+            let mut client = TestClient::setup(url, timeout, cli.node_timeout);
+            if *pin_node_per_account && urls.len() < 2 {
+                warn!("--pin-node-per-account needs a list of nodes, all transactions go to the only one");
+            }
 
-            let chain_id = client.chain_id().unwrap().as_u64();
-            let gas_price = client.gas_price().unwrap();
+            let chain_id = client
+                .chain_id()
+                .ok_or_else(|| Error::NodeUnreachable("cannot get the chain id from the node".to_owned()))?
+                .as_u64();
+            if *validate_source {
+                check_source_chain(source_file, source_chain_id, chain_id, *force)?;
+            }
+            let mut gas_price = client
+                .gas_price()
+                .ok_or_else(|| Error::NodeUnreachable("cannot get the gas price from the node".to_owned()))?;
+            let block_number = client
+                .block_number()
+                .ok_or_else(|| Error::NodeUnreachable("cannot get the latest height from the node".to_owned()))?;
+            let frc20_code = client
+                .frc20_code()
+                .ok_or_else(|| Error::NodeUnreachable("cannot get the frc20 code from the node".to_owned()))?;
             info!("chain_id:     {}", chain_id);
             info!("gas_price:    {}", gas_price);
-            info!("block_number: {}", client.block_number().unwrap());
-            info!("frc20 code:   {:?}", client.frc20_code().unwrap());
+            info!("block_number: {}", block_number);
+            info!("frc20 code:   {:?}", frc20_code);
+            info!("tx type:      {}", tx_type);
+            let base_fee = client.current_block().and_then(|b| b.base_fee_per_gas);
+            match tx_type {
+                TxType::DynamicFee(_) if base_fee.is_none() => {
+                    anyhow::bail!("the chain does not support EIP-1559 transactions, no base fee in the latest block")
+                }
+                TxType::AccessList(_) if base_fee.is_none() => {
+                    warn!("cannot confirm EIP-2930 support of the chain, transactions may be rejected")
+                }
+                _ => {}
+            }
+            if let (TxType::DynamicFee(priority), Some(base_fee)) = (&mut tx_type, base_fee) {
+                match client.max_priority_fee() {
+                    Some(tip) => {
+                        // leaves room for the base fee to double before the transactions are mined
+                        gas_price = base_fee * 2u64 + tip;
+                        *priority = Some(tip);
+                        info!("priority fee: {}, max fee: {}", tip, gas_price);
+                    }
+                    None => {
+                        // the tip is what the gas price pays above the base fee, not the whole gas price
+                        let tip = gas_price.saturating_sub(base_fee);
+                        gas_price = base_fee * 2u64 + tip;
+                        *priority = Some(tip);
+                        warn!(
+                            "no priority fee suggested by the node, priority fee: {} above the base fee, max fee: {}",
+                            tip, gas_price
+                        );
+                    }
+                }
+            }
 
             info!("preparing test data...");
-            let source_keys = source_keys
+            let mut source_keys = pool
                 .par_iter()
-                .filter_map(|kp| {
-                    let (secret, address) = (
-                        secp256k1::SecretKey::from_str(kp.private.as_str()).unwrap(),
-                        Address::from_str(kp.address.as_str()).unwrap(),
-                    );
+                .enumerate()
+                .filter_map(|(idx, (secret, address))| {
+                    let (secret, address) = (*secret, *address);
                     let balance = if *check_balance {
                         client.balance(address, None)
                     } else {
                         U256::MAX
                     };
                     if balance > target_amount.mul(count) {
-                        let target = (0..count)
-                            .map(|_| {
-                                (
-                                    Address::from_str(one_eth_key().address.as_str()).unwrap(),
-                                    target_amount,
-                                )
+                        let target = (0..count as usize)
+                            .map(|r| {
+                                let recipient = match recipients {
+                                    RecipientPattern::SelfTx => address,
+                                    RecipientPattern::Pool => pool[(idx + r + 1) % pool.len()].1,
+                                    RecipientPattern::Fixed(recipient) => recipient,
+                                    RecipientPattern::Random => {
+                                        Address::from_str(one_eth_key().address.as_str()).unwrap()
+                                    }
+                                };
+                                (recipient, target_amount)
                             })
                             .collect::<Vec<_>>();
                         debug!("account {:?} added to source pool", address);
@@ -405,54 +2338,545 @@ fn main() -> anyhow::Result<()> {
                 return Ok(());
             }
 
-            let total_succeed = AtomicU64::new(0);
-            let concurrences = if source_keys.len() > max_pool_size {
+            let seed = seed.unwrap_or_else(new_seed);
+            if let Some(template) = data_template {
+                info!("data template: {}, {} bytes, seed {}", template, template.size(), seed);
+            }
+            // every account still sends its own transactions at consecutive nonces
+            if *shuffle {
+                template::shuffle(&mut source_keys, seed);
+                info!("{} source accounts shuffled, seed {}", source_keys.len(), seed);
+            }
+            // calldata of the index-th transaction, the index counts rounds across source keys
+            let data_of = |sender: &Address, recipient: &Address, index: u64| {
+                data_template
+                    .as_ref()
+                    .map_or_else(Bytes::default, |t| t.expand(sender, recipient, index, seed))
+            };
+            let (_, sender, targets) = &source_keys[0];
+            let sample = data_of(sender, &targets[0].0, 0);
+            let gas_limit = match gas_limit {
+                Some(gas_limit) => *gas_limit,
+                None if data_template.is_some() => {
+                    let req = CallRequest {
+                        from: Some(*sender),
+                        to: Some(targets[0].0),
+                        value: Some(targets[0].1),
+                        data: Some(sample.clone()),
+                        ..Default::default()
+                    };
+                    let estimated = client
+                        .estimate_gas(req)
+                        .map_err(|e| {
+                            anyhow::anyhow!("cannot estimate gas of the data template, use --gas-limit: {}", e)
+                        })?
+                        .as_u64();
+                    // random placeholders may take other code paths than the sample
+                    let gas_limit = estimated + estimated / 5;
+                    info!("estimated gas {}, using {} as the gas limit", estimated, gas_limit);
+                    gas_limit
+                }
+                None => TRANSFER_GAS,
+            };
+            check_gas_limit(gas_limit, &tx_type, &sample.0)?;
+            client.gas_limit = U256::from(gas_limit);
+            // node clients sign with the run gas limit too
+            let pins = (urls.len() > 1).then(|| {
+                let addresses = pool.iter().map(|(_, address)| *address).collect::<Vec<_>>();
+                assign_nodes(
+                    &urls,
+                    &addresses,
+                    (timeout, cli.node_timeout),
+                    client.gas_limit,
+                    *pin_node_per_account,
+                )
+            });
+
+            if *precheck {
+                let addresses = source_keys.iter().map(|(_, address, _)| *address).collect::<Vec<_>>();
+                // every transaction pays its value and at most its gas limit at the run gas price
+                let cost = (target_amount + U256::from(gas_limit) * gas_price) * count;
+                let mut checked = client.balances_and_nonces(&addresses).into_iter();
+                let (mut insufficient, mut unknown) = (0usize, 0usize);
+                source_keys.retain(|(_, address, _)| match checked.next().unwrap_or_default() {
+                    (Some(balance), Some(_)) if balance >= cost => true,
+                    (Some(balance), Some(_)) => {
+                        warn!("{:?} excluded, balance {} is below {}", address, balance, cost);
+                        insufficient += 1;
+                        false
+                    }
+                    _ => {
+                        warn!("{:?} excluded, its balance or nonce is unknown", address);
+                        unknown += 1;
+                        false
+                    }
+                });
+                info!(
+                    "precheck excluded {} of {} accounts, {} with insufficient balance, {} with unknown balance or nonce",
+                    insufficient + unknown,
+                    addresses.len(),
+                    insufficient,
+                    unknown
+                );
+                if source_keys.is_empty() && insufficient > 0 {
+                    return Err(Error::InsufficientFunds("no source account passed the precheck".to_owned()).into());
+                } else if source_keys.is_empty() {
+                    return Err(Error::NodeUnreachable("no source account passed the precheck".to_owned()).into());
+                }
+            }
+            let client = Arc::new(client);
+
+            if *sign_bench {
+                info!("signing {} transactions per source key without sending...", count);
+                let nonces = source_keys
+                    .par_iter()
+                    .map(|(_, address, _)| client.pending_nonce(*address).unwrap_or_default())
+                    .collect::<Vec<_>>();
+                let now = Instant::now();
+                let signed = source_keys
+                    .par_iter()
+                    .zip(nonces.par_iter())
+                    .enumerate()
+                    .map(|(idx, ((source, address, targets), nonce))| {
+                        targets
+                            .iter()
+                            .enumerate()
+                            .filter(|(i, target)| {
+                                let index = (*i * source_keys.len() + idx) as u64;
+                                client
+                                    .sign_simple(
+                                        source,
+                                        target,
+                                        Some(chain_id),
+                                        Some(gas_price),
+                                        Some(*nonce + *i),
+                                        &tx_type,
+                                        data_of(address, &target.0, index),
+                                    )
+                                    .is_ok()
+                            })
+                            .count()
+                    })
+                    .sum::<usize>();
+                let elapsed = now.elapsed().as_secs_f64();
+                report::summary(format!(
+                    "Sign bench summary: signed,{}/{},threads,{},seconds,{:.3},signatures/s,{:.3},type,{}",
+                    signed,
+                    source_keys.len() * count as usize,
+                    max_pool_size,
+                    elapsed,
+                    signed as f64 / elapsed,
+                    tx_type,
+                ));
+                return Ok(());
+            }
+
+            let metrics = Arc::new(Metrics::default());
+            metrics::snapshot_on_signal(metrics.clone())?;
+            let _dashboard = logs
+                .map(|logs| {
+                    let nodes = real_network(network.get_url().as_str()).into_iter().flatten().collect();
+                    Dashboard::start(nodes, (timeout, cli.node_timeout), None, Some(metrics.clone()), logs)
+                })
+                .transpose()?;
+            let mut concurrences = if source_keys.len() > max_pool_size {
                 max_pool_size
             } else {
                 source_keys.len()
             };
+            let total = workload
+                .as_ref()
+                .map_or(source_keys.len() * count as usize, Workload::len);
+            let rounds = if workload.is_some() { 0 } else { count };
+            let mut run_config = json!({
+                "network": network.get_url(),
+                "mode": format!("{:?}", mode),
+                "recipient_pattern": recipients.to_string(),
+                "total": total,
+                "source_keys": source_keys.len(),
+                "count": count,
+                "max_threads": max_par,
+                "concurrency": concurrences,
+                "delay": delay,
+                "block_time": block_time,
+                "tx_type": tx_type.to_string(),
+                "gas_limit": gas_limit,
+                "data_template": data_template.as_ref().map(|t| t.to_string()),
+                "to": to,
+                "seed": (data_template.is_some() || *shuffle).then(|| seed),
+                "shuffle": shuffle,
+                "require_receipt_status": require_receipt_status,
+                "adaptive": adaptive,
+                "tx_deadline": tx_deadline,
+                "on_deadline": tx_deadline.map(|_| format!("{:?}", on_deadline)),
+                "stall_window": stall_window,
+                "on_stall": stall_window.map(|_| format!("{:?}", on_stall)),
+                "max_gas_spend": max_gas_spend.map(|wei| wei.to_string()),
+                "jit_fund": jit_fund,
+                "transactions_from_file": transactions_from_file,
+                "pin_node_per_account": *pin_node_per_account && pins.is_some(),
+                "http_pool_size": http_pool_size,
+            });
+            let mut summary = None;
+            if let Some(path) = summary_json {
+                metrics::summary_on_exit(metrics.clone(), path.clone(), run_config.clone())?;
+                summary = Some(SummaryGuard::new(metrics.clone(), path.clone(), run_config.clone()));
+                if let Some(interval) = metric_flush_interval {
+                    metrics::flush_summary_every(
+                        metrics.clone(),
+                        path.clone(),
+                        run_config.clone(),
+                        Duration::from_secs(*interval),
+                    );
+                }
+            }
+            let mut aimd = adaptive.then(|| {
+                Aimd::new(
+                    Duration::from_millis(*target_latency),
+                    *adaptive_step as usize,
+                    concurrences,
+                )
+            });
+
+            if let Some(window) = stall_window {
+                let addresses = match workload.as_ref() {
+                    Some(workload) => workload.0.keys().map(|idx| pool[*idx].1).collect(),
+                    None => source_keys.iter().map(|(_, address, _)| *address).collect(),
+                };
+                watch_stalls(
+                    client.clone(),
+                    metrics.clone(),
+                    addresses,
+                    total as u64,
+                    Duration::from_secs(*window),
+                    *on_stall,
+                );
+            }
 
             // one-thread per source key
             info!("starting tests...");
-            let start_height = client.block_number().unwrap();
+            let start_height = client
+                .block_number()
+                .ok_or_else(|| Error::NodeUnreachable("cannot get the start height of the run".to_owned()))?;
             let mut last_height = start_height;
-            let total = source_keys.len() * count as usize;
+            let submitted = Mutex::new(HashSet::new());
+            let gas_prices = GasPrices::default();
+            // past the deadline a transaction counts as failed instead of waiting for the confirm timeout
+            let receipt_wait = tx_deadline.map_or(confirm, |deadline| ConfirmOpts {
+                timeout: Duration::from_secs(deadline),
+                ..confirm
+            });
+            let (expired, replaced) = (AtomicU64::new(0), AtomicU64::new(0));
+            let resyncs = AtomicU64::new(0);
+            // every source account pays its value and at most its gas limit at the run gas price
+            let run_cost = (target_amount + client.gas_limit * gas_price) * count;
+            let tx_cost = client.gas_limit * gas_price;
+            let budget = max_gas_spend.map(|wei| GasBudget::new(U256::from(wei)));
+            let root_nonce = Mutex::new(None);
+            // succeeded transactions of every source account, to check the load is spread evenly
+            let per_account = source_keys.iter().map(|_| AtomicU64::new(0)).collect::<Vec<_>>();
+            // fundings and their receipts are left out of the run time
+            if *jit_fund && rounds > 0 {
+                let begin = Instant::now();
+                source_keys.par_iter().for_each(|(_, address, _)| {
+                    fund_just_in_time(
+                        &client,
+                        *address,
+                        run_cost,
+                        chain_id,
+                        gas_price,
+                        &root_nonce,
+                        &confirm,
+                        &metrics,
+                    )
+                });
+                info!("source accounts funded just in time in {:?}", begin.elapsed());
+            }
             let now = std::time::Instant::now();
-            for r in 0..count {
+            metrics.touch();
+            let (mut pauses, mut paused) = (0u64, Duration::ZERO);
+            if let Some(workload) = workload.as_ref() {
+                run_workload(
+                    &client,
+                    pins.as_ref(),
+                    workload,
+                    &pool,
+                    (chain_id, gas_price, &tx_type),
+                    require_receipt_status.then(|| receipt_wait),
+                    &resyncs,
+                    &metrics,
+                );
+            }
+            for r in 0..rounds {
+                if metrics.aborted() {
+                    break;
+                }
+                // waiting for a block or on backpressure is not a stall
                 loop {
-                    let current = client.block_number().unwrap();
+                    let current = client
+                        .block_number()
+                        .ok_or_else(|| Error::NodeUnreachable("cannot get the height to start a round".to_owned()))?;
                     if current > last_height {
                         last_height = current;
                         break;
                     } else {
                         std::thread::sleep(Duration::from_secs(1));
+                        metrics.touch();
+                    }
+                }
+                // pause while the node mempool is above the threshold, polled like receipts
+                if let Some(threshold) = backpressure_threshold {
+                    let begin = Instant::now();
+                    let mut waited = false;
+                    while let Some(pending) = client.pending_tx_count().filter(|p| *p > U256::from(*threshold)) {
+                        if !waited {
+                            info!("{} pending transactions on the node, pausing round {}", pending, r + 1);
+                            waited = true;
+                        }
+                        std::thread::sleep(confirm.poll_interval);
+                        metrics.touch();
+                    }
+                    if waited {
+                        pauses += 1;
+                        paused += begin.elapsed();
+                        info!("round {} resumed after {:?}", r + 1, begin.elapsed());
                     }
                 }
                 let now = std::time::Instant::now();
-                source_keys.par_iter().for_each(|(source, address, targets)| {
+                let round_latency = Mutex::new(stats::Summary::new("round_latency", true));
+                let send = |(idx, (source, address, targets)): (
+                    usize,
+                    &(secp256k1::SecretKey, Address, Vec<(Address, U256)>),
+                )| {
+                    let client = pins
+                        .as_ref()
+                        .and_then(|pins| pins.get(address))
+                        .map_or(client.as_ref(), |c| c.as_ref());
+                    if metrics.aborted() || budget.as_ref().map_or(false, |budget| !budget.reserve(tx_cost)) {
+                        return;
+                    }
                     let target = targets.get(r as usize).unwrap();
-                    if let Some(nonce) = client.pending_nonce(*address) {
-                        if client
-                            .distribution_simple(source, target, Some(chain_id), Some(gas_price), Some(nonce))
-                            .is_ok()
-                        {
-                            total_succeed.fetch_add(1, Relaxed);
+                    let data = data_of(address, &target.0, r * source_keys.len() as u64 + idx as u64);
+                    metrics.begin();
+                    let begin = Instant::now();
+                    let (nonce, sent) = send_resyncing(
+                        &client,
+                        (source, *address),
+                        target,
+                        chain_id,
+                        gas_price,
+                        &tx_type,
+                        &data,
+                        &resyncs,
+                        &metrics,
+                    );
+                    let latency = begin.elapsed();
+                    if let (Some(budget), None) = (budget.as_ref(), sent) {
+                        budget.settle(tx_cost, U256::zero());
+                    }
+                    if let Some(hash) = sent {
+                        if !submitted.lock().unwrap().insert(hash) {
+                            warn!("duplicate submission of {:?} skipped", hash);
+                            metrics.duplicate();
+                            return;
                         }
                     }
-                });
+                    let outcome = match sent {
+                        Some(hash) if *require_receipt_status || tx_deadline.is_some() => {
+                            let (receipt, pending) = client.wait_for_inclusion(hash, &receipt_wait);
+                            if receipt.is_some() {
+                                let mined = Instant::now();
+                                // the mempool wait starts when the send began, the node may see it before answering
+                                metrics.record_inclusion(pending.map(|p| (p - begin, mined - p)));
+                            }
+                            if let (None, Some(nonce)) = (&receipt, nonce.filter(|_| tx_deadline.is_some())) {
+                                expired.fetch_add(1, Relaxed);
+                                let replacement = replace_expired(
+                                    &client,
+                                    *on_deadline,
+                                    (source, *address),
+                                    target,
+                                    chain_id,
+                                    gas_price,
+                                    nonce,
+                                    &tx_type,
+                                    data,
+                                );
+                                if let Some(replacement) = replacement {
+                                    debug!("{:?} past its deadline, replaced by {:?}", hash, replacement);
+                                    replaced.fetch_add(1, Relaxed);
+                                }
+                            }
+                            // legacy receipts have no effective price, it is the price we signed with
+                            if let Some(r) = receipt.as_ref() {
+                                let price = r.effective_gas_price.unwrap_or(gas_price);
+                                gas_prices.record(price.as_u128() as f64);
+                                if let (Some(budget), Some(gas_used)) = (budget.as_ref(), r.gas_used) {
+                                    budget.settle(tx_cost, gas_used * price);
+                                }
+                            }
+                            match receipt.and_then(|r| r.status) {
+                                Some(status) if status == U64::from(1u64) => Outcome::Succeed,
+                                Some(_) => {
+                                    warn_revert(&client, hash);
+                                    Outcome::Reverted
+                                }
+                                None => Outcome::Failed,
+                            }
+                        }
+                        Some(_) => Outcome::Succeed,
+                        None => Outcome::Failed,
+                    };
+                    if outcome == Outcome::Succeed {
+                        per_account[idx].fetch_add(1, Relaxed);
+                    }
+                    metrics.record(outcome, latency);
+                    round_latency.lock().unwrap().add(latency.as_millis() as f64);
+                };
+                match aimd.as_mut() {
+                    Some(aimd) => {
+                        let level = aimd.level();
+                        rayon::ThreadPoolBuilder::new()
+                            .num_threads(level)
+                            .build()?
+                            .install(|| source_keys.par_iter().enumerate().for_each(send));
+                        let p95 = round_latency.lock().unwrap().quantiles()[1];
+                        let next = aimd.update(Duration::from_millis(p95 as u64));
+                        info!(
+                            "round {}/{} concurrency {} latency_ms_p95 {:.0} next {}",
+                            r + 1,
+                            count,
+                            level,
+                            p95,
+                            next
+                        );
+                    }
+                    None => source_keys.par_iter().enumerate().for_each(send),
+                }
                 let elapsed = now.elapsed().as_secs();
                 info!("round {}/{} time {}", r + 1, count, elapsed);
+                if budget.as_ref().map_or(false, |budget| budget.skipped() > 0) {
+                    break;
+                }
                 std::thread::sleep(Duration::from_secs(*delay));
+                metrics.touch();
             }
 
             let elapsed = now.elapsed().as_secs();
-            let end_height = client.block_number().unwrap();
+            let end_height = client
+                .block_number()
+                .ok_or_else(|| Error::NodeUnreachable("cannot get the end height of the run".to_owned()))?;
+            if let Some(aimd) = aimd {
+                let trajectory = aimd.trajectory().iter().map(|l| l.to_string()).collect::<Vec<_>>();
+                info!("adaptive concurrency trajectory: {}", trajectory.join(","));
+                info!("adaptive steady-state concurrency: {}", aimd.steady_state());
+                concurrences = aimd.steady_state();
+            }
 
-            let avg = total as f64 / elapsed as f64;
+            if backpressure_threshold.is_some() {
+                info!("backpressure paused {} rounds, {:?} in total", pauses, paused);
+            }
+            if let Some(report) = gas_prices.report() {
+                info!("{}", report);
+            }
+            if let Some(budget) = budget.as_ref() {
+                info!("{}", budget.report());
+            }
+            if let Some(report) = metrics.inclusion_report() {
+                info!("{}", report);
+            }
+            if *jit_fund {
+                let snapshot = metrics.snapshot();
+                info!(
+                    "{} source accounts funded just in time, {} fundings failed",
+                    snapshot.funded, snapshot.funding_failed
+                );
+            }
+            let mut spread = stats::Summary::new("succeeded_per_account", false);
+            per_account.iter().for_each(|n| spread.add(n.load(Relaxed) as f64));
+            let (fewest, most) = spread.range();
             info!(
-                "Test result summary: total,{:?}/{},concurrency,{},TPS,{:.3},seconds,{},height,{},{}",
-                total_succeed, total, concurrences, avg, elapsed, start_height, end_height,
+                "per account distribution: accounts,{},expected,{},min,{:.0},median,{:.0},max,{:.0},idle,{}",
+                per_account.len(),
+                count,
+                fewest,
+                spread.quantiles()[0],
+                most,
+                per_account.iter().filter(|n| n.load(Relaxed) == 0).count()
             );
+            if resyncs.load(Relaxed) > 0 {
+                warn!(
+                    "{} nonces resynced from the node after a rejection",
+                    resyncs.load(Relaxed)
+                );
+            }
+            if let Some(deadline) = tx_deadline {
+                info!(
+                    "{} transactions hit the deadline of {}s, {} replaced",
+                    expired.load(Relaxed),
+                    deadline,
+                    replaced.load(Relaxed)
+                );
+            }
+            let duplicates = metrics.snapshot().duplicates;
+            if duplicates > 0 {
+                warn!("{} duplicate submissions skipped and not counted", duplicates);
+            }
+
+            let avg = total as f64 / elapsed as f64;
+            report::summary(format!(
+                "Test result summary: total,{}/{},concurrency,{},TPS,{:.3},seconds,{},height,{},{},type,{}",
+                metrics.succeed(),
+                total,
+                concurrences,
+                avg,
+                elapsed,
+                start_height,
+                end_height,
+                tx_type,
+            ));
+            if let Some(summary) = summary {
+                run_config["concurrency"] = json!(concurrences);
+                run_config["start_height"] = json!(start_height);
+                run_config["end_height"] = json!(end_height);
+                summary.finish(run_config, !metrics.aborted())?;
+            }
+            if metrics.aborted() {
+                anyhow::bail!("run aborted, no transaction completed within the stall window");
+            }
+
+            let snapshot = metrics.snapshot();
+            let failed = asserts
+                .iter()
+                .filter(|a| match a.check(&snapshot) {
+                    Some(actual) => {
+                        error!("assertion {} failed, actual {:.3}", a, actual);
+                        true
+                    }
+                    None => {
+                        report::summary(format!("assertion {} passed", a));
+                        false
+                    }
+                })
+                .count();
+            let regressions = match baseline {
+                Some(baseline) => {
+                    let deltas = baseline::compare(&baseline, &snapshot, *regression_pct);
+                    baseline::print(&deltas);
+                    deltas.iter().filter(|d| d.regressed).count()
+                }
+                None => 0,
+            };
+            if failed > 0 {
+                anyhow::bail!("{} of {} assertions failed", failed, asserts.len());
+            }
+            if regressions > 0 {
+                anyhow::bail!(
+                    "{} metrics regressed more than {}% from the baseline",
+                    regressions,
+                    regression_pct
+                );
+            }
             Ok(())
         }
         None => Ok(()),
@@ -0,0 +1,191 @@
+//! Parameter sweeps of the test command
+//!
+//! Every combination of the swept values runs as its own `test` process, one after another,
+//! so nonces are fetched afresh and each run writes its own summary file.
+
+use crate::{baseline, metrics::Snapshot};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// test options set by the matrix itself
+const RESERVED: [&str; 2] = ["--summary-json", "--tui"];
+
+/// `--config` of the matrix command, e.g.
+/// `{"args": ["--network", "local", "--mode", "basic", "--count", "10"], "params": {"max-threads": [50, 100, 200]}}`
+#[derive(Debug, Deserialize)]
+pub struct MatrixConfig {
+    /// test arguments shared by every run
+    #[serde(default)]
+    args: Vec<String>,
+    /// values of every swept test option, by long name without dashes,
+    /// `true` passes a switch and `false` leaves it out
+    params: BTreeMap<String, Vec<Value>>,
+}
+
+/// One finished run of the matrix
+#[derive(Debug)]
+pub struct Run {
+    pub params: Vec<(String, Value)>,
+    pub summary: PathBuf,
+    /// none if the run wrote no summary
+    pub metrics: Option<Snapshot>,
+}
+
+impl MatrixConfig {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let config: Self = serde_json::from_str(std::fs::read_to_string(path)?.as_str())
+            .map_err(|e| anyhow::anyhow!("invalid matrix config {:?}: {}", path, e))?;
+        if let Some(arg) = config
+            .args
+            .iter()
+            .map(String::as_str)
+            .chain(config.params.keys().map(|k| k.trim_start_matches('-')))
+            .find(|arg| RESERVED.contains(arg) || RESERVED.contains(&format!("--{}", arg).as_str()))
+        {
+            anyhow::bail!("{} is set by the matrix and cannot be configured", arg);
+        }
+        if let Some((name, _)) = config.params.iter().find(|(_, values)| values.is_empty()) {
+            anyhow::bail!("parameter {} of the matrix has no value", name);
+        }
+        Ok(config)
+    }
+
+    /// swept parameter names, in the order of the matrix columns
+    pub fn names(&self) -> Vec<&str> {
+        self.params.keys().map(String::as_str).collect()
+    }
+
+    /// every combination of the swept values, the last parameter changes fastest
+    pub fn combinations(&self) -> Vec<Vec<(String, Value)>> {
+        self.params.iter().fold(vec![vec![]], |combinations, (name, values)| {
+            combinations
+                .iter()
+                .flat_map(|c| {
+                    values.iter().map(move |v| {
+                        let mut c = c.clone();
+                        c.push((name.clone(), v.clone()));
+                        c
+                    })
+                })
+                .collect()
+        })
+    }
+}
+
+fn option_args(params: &[(String, Value)]) -> Vec<String> {
+    params
+        .iter()
+        .flat_map(|(name, value)| {
+            let flag = format!("--{}", name.trim_start_matches('-'));
+            match value {
+                Value::Bool(true) => vec![flag],
+                Value::Bool(false) | Value::Null => vec![],
+                Value::String(s) => vec![flag, s.clone()],
+                v => vec![flag, v.to_string()],
+            }
+        })
+        .collect()
+}
+
+/// run every combination sequentially, keeping their summaries in `out_dir`
+pub fn run(config: &MatrixConfig, out_dir: &Path) -> anyhow::Result<Vec<Run>> {
+    std::fs::create_dir_all(out_dir)?;
+    let exe = std::env::current_exe()?;
+    let combinations = config.combinations();
+    let mut runs = vec![];
+    for (idx, params) in combinations.into_iter().enumerate() {
+        let summary = out_dir.join(format!("run-{:03}.json", idx + 1));
+        let args = option_args(&params);
+        log::info!(
+            "matrix run {}: test {} {}",
+            idx + 1,
+            config.args.join(" "),
+            args.join(" ")
+        );
+        // a stale summary of a previous sweep must not stand in for a run that wrote none
+        if summary.exists() {
+            std::fs::remove_file(&summary)?;
+        }
+        let status = Command::new(&exe)
+            .arg("test")
+            .args(&config.args)
+            .args(&args)
+            .arg("--summary-json")
+            .arg(&summary)
+            .status()?;
+        if !status.success() {
+            log::warn!("matrix run {} exited with {}", idx + 1, status);
+        }
+        let metrics = summary
+            .exists()
+            .then(|| baseline::load(&summary))
+            .transpose()
+            .unwrap_or_else(|e| {
+                log::warn!("{}", e);
+                None
+            });
+        runs.push(Run {
+            params,
+            summary,
+            metrics,
+        });
+    }
+    Ok(runs)
+}
+
+/// matrix columns after the swept parameters
+pub const METRIC_HEADER: [&str; 6] = [
+    "tps",
+    "p50_latency_ms",
+    "p95_latency_ms",
+    "error_rate",
+    "succeed",
+    "sent",
+];
+
+/// one row per run, the swept values followed by [`METRIC_HEADER`], empty metrics for failed runs
+pub fn rows(runs: &[Run]) -> Vec<Vec<Value>> {
+    runs.iter()
+        .map(|run| {
+            let mut row = run.params.iter().map(|(_, v)| v.clone()).collect::<Vec<_>>();
+            match &run.metrics {
+                Some(m) => row.extend([
+                    json!(m.tps),
+                    json!(m.latency_p50),
+                    json!(m.latency_p95),
+                    json!(m.error_rate),
+                    json!(m.succeed),
+                    json!(m.sent),
+                ]),
+                None => row.extend(METRIC_HEADER.iter().map(|_| Value::Null)),
+            }
+            row
+        })
+        .collect()
+}
+
+/// print the comparison matrix
+pub fn print(names: &[&str], runs: &[Run]) {
+    let header = names.iter().chain(METRIC_HEADER.iter()).collect::<Vec<_>>();
+    log::info!("{}", header.iter().map(|h| format!("{:>16}", h)).collect::<String>());
+    for (run, row) in runs.iter().zip(rows(runs)) {
+        let line = row
+            .iter()
+            .map(|v| match v {
+                Value::Null => format!("{:>16}", "-"),
+                Value::String(s) => format!("{:>16}", s),
+                Value::Number(n) if n.is_f64() => format!("{:>16.3}", n.as_f64().unwrap_or_default()),
+                v => format!("{:>16}", v.to_string()),
+            })
+            .collect::<String>();
+        match run.metrics {
+            Some(_) => log::info!("{}", line),
+            None => log::error!("{} no summary in {:?}", line, run.summary),
+        }
+    }
+}
@@ -0,0 +1,549 @@
+use crate::stats::Summary;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::VecDeque,
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering::Relaxed},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use web3::types::U256;
+
+/// Live counters of a test run, shared between the sending threads
+#[derive(Debug)]
+pub struct Metrics {
+    start: Instant,
+    // transactions whose sending has begun, the ones not recorded yet are in flight
+    begun: AtomicU64,
+    sent: AtomicU64,
+    succeed: AtomicU64,
+    reverted: AtomicU64,
+    failed: AtomicU64,
+    // submissions of an already submitted hash, not counted as sent
+    duplicates: AtomicU64,
+    // milliseconds spent sending a transaction
+    latency: Mutex<Summary>,
+    // total of the latencies, to average them over an interval
+    latency_sum: AtomicU64,
+    // milliseconds from the submission until the node reports the transaction pending
+    mempool_wait: Mutex<Summary>,
+    // milliseconds from pending until mined
+    inclusion_delay: Mutex<Summary>,
+    // mined transactions the node never reported pending
+    unseen_pending: AtomicU64,
+    // source accounts topped up during the run, not part of the load
+    funded: AtomicU64,
+    funding_failed: AtomicU64,
+    // whether the summary of the completed run is written, partial summaries must not overwrite it
+    summary_completed: Mutex<bool>,
+    // when the last outcome was recorded, the start of the run before the first one
+    last_completion: Mutex<Instant>,
+    // the last error of a transaction that could not be sent
+    last_error: Mutex<Option<String>>,
+    // set by the stall or tps floor watchdog, the run stops sending and returns an error
+    aborted: AtomicBool,
+}
+
+/// What happened to a sent transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Succeed,
+    /// mined with receipt status 0
+    Reverted,
+    /// not sent or not confirmed
+    Failed,
+}
+
+/// Point-in-time view of [`Metrics`]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub elapsed: u64,
+    pub sent: u64,
+    pub succeed: u64,
+    pub reverted: u64,
+    pub failed: u64,
+    pub duplicates: u64,
+    pub tps: f64,
+    pub error_rate: f64,
+    pub latency_p50: f64,
+    pub latency_p95: f64,
+    /// none if no transaction was seen pending, e.g. the node hides its mempool
+    #[serde(default)]
+    pub mempool_wait_p50: Option<f64>,
+    #[serde(default)]
+    pub mempool_wait_p95: Option<f64>,
+    #[serde(default)]
+    pub inclusion_delay_p50: Option<f64>,
+    #[serde(default)]
+    pub inclusion_delay_p95: Option<f64>,
+    /// just-in-time fundings, not counted as sent
+    #[serde(default)]
+    pub funded: u64,
+    #[serde(default)]
+    pub funding_failed: u64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            begun: AtomicU64::new(0),
+            sent: AtomicU64::new(0),
+            succeed: AtomicU64::new(0),
+            reverted: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            duplicates: AtomicU64::new(0),
+            latency: Mutex::new(Summary::new("latency", true)),
+            latency_sum: AtomicU64::new(0),
+            mempool_wait: Mutex::new(Summary::new("mempool_wait", true)),
+            inclusion_delay: Mutex::new(Summary::new("inclusion_delay", true)),
+            unseen_pending: AtomicU64::new(0),
+            funded: AtomicU64::new(0),
+            funding_failed: AtomicU64::new(0),
+            summary_completed: Mutex::new(false),
+            last_completion: Mutex::new(Instant::now()),
+            last_error: Mutex::new(None),
+            aborted: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Metrics {
+    /// mark a transaction as in flight until its outcome is recorded
+    pub fn begin(&self) {
+        self.begun.fetch_add(1, Relaxed);
+    }
+
+    pub fn inflight(&self) -> u64 {
+        let done = self.sent.load(Relaxed) + self.duplicates.load(Relaxed);
+        self.begun.load(Relaxed).saturating_sub(done)
+    }
+
+    /// total milliseconds spent sending the recorded transactions
+    pub fn latency_sum(&self) -> u64 {
+        self.latency_sum.load(Relaxed)
+    }
+
+    /// record the outcome of sending one transaction
+    pub fn record(&self, outcome: Outcome, latency: Duration) {
+        self.sent.fetch_add(1, Relaxed);
+        match outcome {
+            Outcome::Succeed => self.succeed.fetch_add(1, Relaxed),
+            Outcome::Reverted => self.reverted.fetch_add(1, Relaxed),
+            Outcome::Failed => self.failed.fetch_add(1, Relaxed),
+        };
+        self.latency.lock().unwrap().add(latency.as_millis() as f64);
+        self.latency_sum.fetch_add(latency.as_millis() as u64, Relaxed);
+        *self.last_completion.lock().unwrap() = Instant::now();
+    }
+
+    /// time since the last recorded outcome
+    pub fn idle(&self) -> Duration {
+        self.last_completion.lock().unwrap().elapsed()
+    }
+
+    /// restart the idle time, when the run starts or pauses on purpose
+    pub fn touch(&self) {
+        *self.last_completion.lock().unwrap() = Instant::now();
+    }
+
+    /// stop the run, the remaining transactions are not sent
+    pub fn abort(&self) {
+        self.aborted.store(true, Relaxed);
+    }
+
+    pub fn aborted(&self) -> bool {
+        self.aborted.load(Relaxed)
+    }
+
+    /// keep the error of a transaction that could not be sent, for stall diagnostics
+    pub fn record_error(&self, error: String) {
+        *self.last_error.lock().unwrap() = Some(error);
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    /// record how long a mined transaction waited in the mempool and then for a block,
+    /// `None` if the node never reported it pending
+    pub fn record_inclusion(&self, waits: Option<(Duration, Duration)>) {
+        match waits {
+            Some((mempool_wait, inclusion_delay)) => {
+                self.mempool_wait.lock().unwrap().add(mempool_wait.as_millis() as f64);
+                self.inclusion_delay
+                    .lock()
+                    .unwrap()
+                    .add(inclusion_delay.as_millis() as f64);
+            }
+            None => {
+                self.unseen_pending.fetch_add(1, Relaxed);
+            }
+        }
+    }
+
+    /// `seen,unseen,mempool_ms_p50,...` of the mined transactions, none if no inclusion is recorded
+    pub fn inclusion_report(&self) -> Option<String> {
+        let mempool_wait = self.mempool_wait.lock().unwrap();
+        let inclusion_delay = self.inclusion_delay.lock().unwrap();
+        let unseen = self.unseen_pending.load(Relaxed);
+        if mempool_wait.count() == 0 && unseen == 0 {
+            return None;
+        }
+        let (mempool, inclusion) = (mempool_wait.quantiles(), inclusion_delay.quantiles());
+        Some(format!(
+            "inclusion,seen_pending,{},unseen_pending,{},mempool_ms_p50,{:.0},mempool_ms_p95,{:.0},inclusion_ms_p50,{:.0},inclusion_ms_p95,{:.0}",
+            mempool_wait.count(),
+            unseen,
+            mempool[0],
+            mempool[1],
+            inclusion[0],
+            inclusion[1]
+        ))
+    }
+
+    /// record the funding of a source account, kept apart from the load transactions
+    pub fn record_funding(&self, ok: bool) {
+        if ok {
+            self.funded.fetch_add(1, Relaxed);
+        } else {
+            self.funding_failed.fetch_add(1, Relaxed);
+        }
+    }
+
+    /// record a transaction whose hash was already submitted in this run
+    pub fn duplicate(&self) {
+        self.duplicates.fetch_add(1, Relaxed);
+    }
+
+    pub fn succeed(&self) -> u64 {
+        self.succeed.load(Relaxed)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let elapsed = self.start.elapsed().as_secs();
+        let sent = self.sent.load(Relaxed);
+        let succeed = self.succeed.load(Relaxed);
+        let reverted = self.reverted.load(Relaxed);
+        let failed = self.failed.load(Relaxed);
+        let duplicates = self.duplicates.load(Relaxed);
+        let quantiles = self.latency.lock().unwrap().quantiles();
+        let (mempool_wait, inclusion_delay) = (
+            quantiles_of(&self.mempool_wait.lock().unwrap()),
+            quantiles_of(&self.inclusion_delay.lock().unwrap()),
+        );
+        Snapshot {
+            elapsed,
+            sent,
+            succeed,
+            reverted,
+            failed,
+            duplicates,
+            tps: if elapsed == 0 {
+                0f64
+            } else {
+                succeed as f64 / elapsed as f64
+            },
+            error_rate: if sent == 0 {
+                0f64
+            } else {
+                (reverted + failed) as f64 / sent as f64
+            },
+            latency_p50: quantiles[0],
+            latency_p95: quantiles[1],
+            mempool_wait_p50: mempool_wait.as_ref().map(|q| q[0]),
+            mempool_wait_p95: mempool_wait.as_ref().map(|q| q[1]),
+            inclusion_delay_p50: inclusion_delay.as_ref().map(|q| q[0]),
+            inclusion_delay_p95: inclusion_delay.as_ref().map(|q| q[1]),
+            funded: self.funded.load(Relaxed),
+            funding_failed: self.funding_failed.load(Relaxed),
+        }
+    }
+}
+
+fn quantiles_of(summary: &Summary) -> Option<Vec<f64>> {
+    (summary.count() > 0).then(|| summary.quantiles())
+}
+
+impl Display for Snapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "snapshot,seconds,{},sent,{},succeed,{},reverted,{},failed,{},duplicates,{},TPS,{:.3},error_rate,{:.3},latency_ms_p50,{:.0},latency_ms_p95,{:.0}",
+            self.elapsed,
+            self.sent,
+            self.succeed,
+            self.reverted,
+            self.failed,
+            self.duplicates,
+            self.tps,
+            self.error_rate,
+            self.latency_p50,
+            self.latency_p95
+        )?;
+        if let (Some(mempool_p50), Some(mempool_p95), Some(inclusion_p50), Some(inclusion_p95)) = (
+            self.mempool_wait_p50,
+            self.mempool_wait_p95,
+            self.inclusion_delay_p50,
+            self.inclusion_delay_p95,
+        ) {
+            write!(
+                f,
+                ",mempool_ms_p50,{:.0},mempool_ms_p95,{:.0},inclusion_ms_p50,{:.0},inclusion_ms_p95,{:.0}",
+                mempool_p50, mempool_p95, inclusion_p50, inclusion_p95
+            )?;
+        }
+        if self.funded + self.funding_failed > 0 {
+            write!(f, ",funded,{},funding_failed,{}", self.funded, self.funding_failed)?;
+        }
+        Ok(())
+    }
+}
+
+/// effective gas prices the rolling median is taken over
+const GAS_PRICE_WINDOW: usize = 100;
+/// a price this many times the rolling median is a spike
+const GAS_PRICE_SPIKE: f64 = 2.0;
+
+/// Effective gas prices of the included transactions of a run, in wei
+#[derive(Debug)]
+pub struct GasPrices {
+    recent: Mutex<(VecDeque<f64>, Summary)>,
+    spikes: AtomicU64,
+}
+
+impl Default for GasPrices {
+    fn default() -> Self {
+        Self {
+            recent: Mutex::new((
+                VecDeque::with_capacity(GAS_PRICE_WINDOW),
+                Summary::new("effective_gas_price", true),
+            )),
+            spikes: AtomicU64::new(0),
+        }
+    }
+}
+
+impl GasPrices {
+    /// record the effective gas price of an included transaction, warning when it spikes
+    pub fn record(&self, price: f64) {
+        let mut guard = self.recent.lock().unwrap();
+        let (recent, summary) = &mut *guard;
+        let mut sorted = recent.iter().copied().collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        if let Some(median) = sorted.get(sorted.len() / 2).filter(|m| **m > 0f64) {
+            if price > median * GAS_PRICE_SPIKE {
+                self.spikes.fetch_add(1, Relaxed);
+                log::warn!(
+                    "effective gas price {:.0} is {:.1}x the rolling median {:.0}, the mempool may be congested",
+                    price,
+                    price / median,
+                    median
+                );
+            }
+        }
+        if recent.len() == GAS_PRICE_WINDOW {
+            recent.pop_front();
+        }
+        recent.push_back(price);
+        summary.add(price);
+    }
+
+    /// `min,median,max,spikes` of the run, none if no price is recorded
+    pub fn report(&self) -> Option<String> {
+        let guard = self.recent.lock().unwrap();
+        let summary = &guard.1;
+        if summary.count() == 0 {
+            return None;
+        }
+        let (min, max) = summary.range();
+        Some(format!(
+            "effective gas price,min,{:.0},median,{:.0},max,{:.0},spikes,{}",
+            min,
+            summary.quantiles()[0],
+            max,
+            self.spikes.load(Relaxed)
+        ))
+    }
+}
+
+/// Gas a run may pay, in wei, the in-flight transactions count for the most they may pay
+#[derive(Debug)]
+pub struct GasBudget {
+    budget: U256,
+    // paid by the mined transactions plus reserved by the in-flight ones
+    spent: Mutex<U256>,
+    skipped: AtomicU64,
+}
+
+impl GasBudget {
+    pub fn new(budget: U256) -> Self {
+        Self {
+            budget,
+            spent: Mutex::new(U256::zero()),
+            skipped: AtomicU64::new(0),
+        }
+    }
+
+    /// reserve the most a transaction may pay, false and counted as skipped if it would exceed the budget
+    pub fn reserve(&self, cost: U256) -> bool {
+        let mut spent = self.spent.lock().unwrap();
+        if *spent + cost > self.budget {
+            if self.skipped.fetch_add(1, Relaxed) == 0 {
+                log::warn!(
+                    "gas budget of {} wei reached, no more transactions are sent",
+                    self.budget
+                );
+            }
+            return false;
+        }
+        *spent += cost;
+        true
+    }
+
+    /// replace a reservation by what the transaction paid, zero if it was never sent
+    pub fn settle(&self, cost: U256, paid: U256) {
+        let mut spent = self.spent.lock().unwrap();
+        *spent = (*spent + paid).saturating_sub(cost);
+    }
+
+    /// transactions skipped to stay within the budget
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Relaxed)
+    }
+
+    /// `spent,budget,skipped`, the spend includes the reservations of transactions without a receipt
+    pub fn report(&self) -> String {
+        format!(
+            "gas spend,{},budget,{},skipped,{}",
+            self.spent.lock().unwrap(),
+            self.budget,
+            self.skipped()
+        )
+    }
+}
+
+/// write the whole run outcome as one json object, `completed` is false for interrupted runs
+pub fn write_summary(path: &Path, metrics: &Metrics, config: &Value, completed: bool) -> std::io::Result<()> {
+    if save_summary(path, metrics, config, completed)? {
+        log::info!("run summary written to {:?}", path);
+    }
+    Ok(())
+}
+
+/// write the summary through a temporary file so a crash never leaves a partial one,
+/// false if the summary of the completed run is already written
+fn save_summary(path: &Path, metrics: &Metrics, config: &Value, completed: bool) -> std::io::Result<bool> {
+    let mut written = metrics.summary_completed.lock().unwrap();
+    if *written {
+        return Ok(false);
+    }
+    let summary = json!({
+        "completed": completed,
+        "metrics": metrics.snapshot(),
+        "config": config,
+    });
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(&summary)?)?;
+    std::fs::rename(&tmp, path)?;
+    *written = completed;
+    Ok(true)
+}
+
+/// Summary of a running test, written as interrupted when dropped before the run finishes,
+/// so a run failing with an error still leaves its metrics
+pub struct SummaryGuard {
+    metrics: Arc<Metrics>,
+    path: PathBuf,
+    config: Value,
+    finished: bool,
+}
+
+impl SummaryGuard {
+    pub fn new(metrics: Arc<Metrics>, path: PathBuf, config: Value) -> Self {
+        Self {
+            metrics,
+            path,
+            config,
+            finished: false,
+        }
+    }
+
+    /// write the summary of the finished run with its final `config`
+    pub fn finish(mut self, config: Value, completed: bool) -> std::io::Result<()> {
+        self.finished = true;
+        write_summary(&self.path, &self.metrics, &config, completed)
+    }
+}
+
+impl Drop for SummaryGuard {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if let Err(e) = write_summary(&self.path, &self.metrics, &self.config, false) {
+            log::error!("failed to write run summary to {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// rewrite the summary of the running test every `interval`, a crash keeps the metrics of the last flush
+pub fn flush_summary_every(metrics: Arc<Metrics>, path: PathBuf, config: Value, interval: Duration) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        match save_summary(&path, &metrics, &config, false) {
+            Ok(true) => log::debug!("partial run summary flushed to {:?}", path),
+            Ok(false) => break,
+            Err(e) => log::warn!("failed to flush the run summary to {:?}: {}", path, e),
+        }
+    });
+}
+
+/// write the run summary before exiting on SIGINT or SIGTERM
+#[cfg(unix)]
+pub fn summary_on_exit(metrics: Arc<Metrics>, path: PathBuf, config: Value) -> std::io::Result<()> {
+    use signal_hook::{
+        consts::{SIGINT, SIGTERM},
+        iterator::Signals,
+    };
+
+    let mut signals = Signals::new(&[SIGINT, SIGTERM])?;
+    std::thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            if let Err(e) = write_summary(&path, &metrics, &config, false) {
+                log::error!("failed to write run summary to {:?}: {}", path, e);
+            }
+            std::process::exit(128 + signal);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn summary_on_exit(_metrics: Arc<Metrics>, _path: PathBuf, _config: Value) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// print a metrics snapshot to stderr every time SIGUSR1 is received
+#[cfg(unix)]
+pub fn snapshot_on_signal(metrics: Arc<Metrics>) -> std::io::Result<()> {
+    use signal_hook::{consts::SIGUSR1, iterator::Signals};
+
+    let mut signals = Signals::new(&[SIGUSR1])?;
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            eprintln!("{}", metrics.snapshot());
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn snapshot_on_signal(_metrics: Arc<Metrics>) -> std::io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use chrono::NaiveDateTime;
+use feth::error::{Error, Result};
+use serde_json::{Map, Value};
+use std::{
+    cmp::Ordering,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use web3::types::H256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+    Txt,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "txt" => Ok(Self::Txt),
+            _ => Err("Invalid format: json, csv and txt are supported".to_owned()),
+        }
+    }
+}
+
+/// When a new output file is started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotate {
+    /// every N blocks
+    Blocks(u64),
+    /// every N minutes of block timestamps
+    Minutes(u64),
+}
+
+impl std::str::FromStr for Rotate {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid rotation {}, expect N blocks or Nmin minutes, e.g. 10000 or 60min",
+                s
+            )
+        };
+        let (n, rotate): (&str, fn(u64) -> Self) = match s.trim().strip_suffix("min") {
+            Some(n) => (n, Self::Minutes),
+            None => (s.trim(), Self::Blocks),
+        };
+        match n.parse::<u64>() {
+            Ok(n) if n > 0 => Ok(rotate(n)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// How transaction and block hashes are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashEncoding {
+    /// hex with a `0x` prefix
+    Hex0x,
+    Hex,
+    Base64,
+}
+
+impl std::str::FromStr for HashEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hex0x" => Ok(Self::Hex0x),
+            "hex" => Ok(Self::Hex),
+            "base64" => Ok(Self::Base64),
+            _ => Err("Invalid hash encoding: hex0x, hex and base64 are supported".to_owned()),
+        }
+    }
+}
+
+impl HashEncoding {
+    pub fn encode(&self, hash: &H256) -> String {
+        match self {
+            Self::Hex0x => format!("0x{}", hex::encode(hash.as_bytes())),
+            Self::Hex => hex::encode(hash.as_bytes()),
+            Self::Base64 => base64::encode(hash.as_bytes()),
+        }
+    }
+}
+
+/// Where and how command results are written
+#[derive(Debug, Clone)]
+pub struct Output {
+    path: PathBuf,
+    format: Format,
+}
+
+impl Output {
+    /// use the explicit format if given, otherwise infer it from the file extension
+    pub fn new(path: &Path, format: Option<Format>) -> Result<Self> {
+        let format = match format {
+            Some(format) => format,
+            None => path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(|ext| ext.parse::<Format>().ok())
+                .ok_or_else(|| {
+                    Error::NotSupport(format!("cannot infer output format of {:?}, please use --output", path))
+                })?,
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+        })
+    }
+
+    /// build an output from optional `--output-file` and `--output` arguments
+    pub fn from_args(path: &Option<PathBuf>, format: Option<Format>) -> Result<Option<Self>> {
+        path.as_ref().map(|path| Self::new(path, format)).transpose()
+    }
+
+    /// write rows of values, each row has one value per header column
+    pub fn write(&self, header: &[&str], rows: &[Vec<Value>]) -> Result<()> {
+        let mut file = std::fs::File::create(&self.path)?;
+        match self.format {
+            Format::Json => {
+                let objects = rows
+                    .iter()
+                    .map(|row| {
+                        header
+                            .iter()
+                            .zip(row.iter())
+                            .map(|(k, v)| (k.to_string(), v.clone()))
+                            .collect::<Map<_, _>>()
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::to_writer_pretty(&mut file, &objects).map_err(std::io::Error::from)?;
+            }
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(file);
+                writer.write_record(header).map_err(std::io::Error::from)?;
+                for row in rows {
+                    writer
+                        .write_record(row.iter().map(plain))
+                        .map_err(std::io::Error::from)?;
+                }
+                writer.flush()?;
+            }
+            Format::Txt => {
+                for row in rows {
+                    writeln!(file, "{}", line(row))?;
+                }
+            }
+        }
+        log::info!("{} rows written to {:?}", rows.len(), self.path);
+        Ok(())
+    }
+
+    /// write rows to a new file every rotation period, each with its own header,
+    /// `key` gives the height and unix timestamp of a row
+    pub fn write_rotated<F>(&self, header: &[&str], rows: &[Vec<Value>], rotate: Rotate, key: F) -> Result<()>
+    where
+        F: Fn(&[Value]) -> (u64, u64),
+    {
+        let mut begin = 0;
+        while begin < rows.len() {
+            let (height, timestamp) = key(&rows[begin]);
+            let len = rows[begin..]
+                .iter()
+                .position(|row| {
+                    let (h, t) = key(row);
+                    match rotate {
+                        Rotate::Blocks(n) => h >= height + n,
+                        Rotate::Minutes(n) => t >= timestamp + n * 60,
+                    }
+                })
+                .unwrap_or(rows.len() - begin);
+            let start = match rotate {
+                Rotate::Blocks(_) => height.to_string(),
+                Rotate::Minutes(_) => NaiveDateTime::from_timestamp_opt(timestamp as i64, 0)
+                    .map_or_else(|| timestamp.to_string(), |dt| dt.format("%Y%m%dT%H%M%S").to_string()),
+            };
+            self.part(&start).write(header, &rows[begin..begin + len])?;
+            begin += len;
+        }
+        Ok(())
+    }
+
+    /// `blocks.csv` becomes `blocks.<start>.csv`
+    fn part(&self, start: &str) -> Self {
+        let stem = self
+            .path
+            .file_stem()
+            .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let name = match self.path.extension() {
+            Some(ext) => format!("{}.{}.{}", stem, start, ext.to_string_lossy()),
+            None => format!("{}.{}", stem, start),
+        };
+        Self {
+            path: self.path.with_file_name(name),
+            format: self.format,
+        }
+    }
+}
+
+/// index of a column, an unknown one lists the available ones
+pub fn column(header: &[&str], name: &str) -> Result<usize> {
+    header
+        .iter()
+        .position(|h| *h == name)
+        .ok_or_else(|| Error::NotSupport(format!("unknown column {}, expect one of {}", name, header.join(", "))))
+}
+
+/// sort rows by a column, numbers and numeric strings by value, the sort is stable so rows with
+/// equal values keep their order
+pub fn sort_rows(header: &[&str], rows: &mut [Vec<Value>], name: &str, reverse: bool) -> Result<()> {
+    let idx = column(header, name)?;
+    let key = |row: &[Value]| match row.get(idx) {
+        Some(Value::Number(n)) => n.as_f64(),
+        Some(Value::String(s)) => s.parse::<f64>().ok(),
+        _ => None,
+    };
+    rows.sort_by(|a, b| {
+        let order = key(a).partial_cmp(&key(b)).unwrap_or(Ordering::Equal);
+        if reverse {
+            order.reverse()
+        } else {
+            order
+        }
+    });
+    Ok(())
+}
+
+/// keep the first `limit` rows, noting how many were left out
+pub fn truncate_rows(rows: &mut Vec<Vec<Value>>, limit: Option<u64>) {
+    if let Some(limit) = limit.filter(|limit| (*limit as usize) < rows.len()) {
+        log::info!("output truncated to {} of {} rows", limit, rows.len());
+        rows.truncate(limit as usize);
+    }
+}
+
+/// a row as comma separated values
+pub fn line(row: &[Value]) -> String {
+    row.iter().map(plain).collect::<Vec<_>>().join(",")
+}
+
+/// render a value without json quoting
+fn plain(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
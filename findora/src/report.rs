@@ -0,0 +1,26 @@
+//! Final summaries of a command, the only lines left with `--quiet`
+
+use crate::color;
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicBool, Ordering::Relaxed},
+};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Relaxed)
+}
+
+/// log a summary line, or print it alone to stdout in quiet mode so scripts can capture it
+pub fn summary(line: impl Display) {
+    if quiet() {
+        println!("{}", line);
+    } else {
+        log::info!("{}", color::success(line));
+    }
+}
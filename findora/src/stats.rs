@@ -0,0 +1,251 @@
+use std::fmt::{Display, Formatter};
+
+/// quantiles reported in a summary line
+const QUANTILES: [f64; 2] = [0.5, 0.95];
+
+/// P² streaming quantile estimator (Jain & Chlamtac, 1985)
+///
+/// Keeps five markers per quantile, so memory is constant regardless of the sample count.
+#[derive(Debug, Clone)]
+struct P2 {
+    p: f64,
+    count: usize,
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl P2 {
+    fn new(p: f64) -> Self {
+        Self {
+            p,
+            count: 0,
+            heights: [0f64; 5],
+            positions: [1f64, 2f64, 3f64, 4f64, 5f64],
+            desired: [1f64, 1f64 + 2f64 * p, 1f64 + 4f64 * p, 3f64 + 2f64 * p, 5f64],
+            increments: [0f64, p / 2f64, p, (1f64 + p) / 2f64, 1f64],
+        }
+    }
+
+    fn add(&mut self, x: f64) {
+        if self.count < 5 {
+            self.heights[self.count] = x;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(|a, b| a.total_cmp(b));
+            }
+            return;
+        }
+        self.count += 1;
+
+        let k = if x < self.heights[0] {
+            self.heights[0] = x;
+            0
+        } else if x >= self.heights[4] {
+            self.heights[4] = x;
+            3
+        } else {
+            (1..5).find(|&i| x < self.heights[i]).unwrap() - 1
+        };
+        self.positions.iter_mut().skip(k + 1).for_each(|n| *n += 1f64);
+        self.desired
+            .iter_mut()
+            .zip(self.increments.iter())
+            .for_each(|(d, inc)| *d += inc);
+
+        for i in 1..4 {
+            let d = self.desired[i] - self.positions[i];
+            if (d >= 1f64 && self.positions[i + 1] - self.positions[i] > 1f64)
+                || (d <= -1f64 && self.positions[i - 1] - self.positions[i] < -1f64)
+            {
+                let d = d.signum();
+                let q = self.parabolic(i, d);
+                self.heights[i] = if self.heights[i - 1] < q && q < self.heights[i + 1] {
+                    q
+                } else {
+                    self.linear(i, d)
+                };
+                self.positions[i] += d;
+            }
+        }
+    }
+
+    fn parabolic(&self, i: usize, d: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + d / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, d: f64) -> f64 {
+        let j = if d > 0f64 { i + 1 } else { i - 1 };
+        self.heights[i] + d * (self.heights[j] - self.heights[i]) / (self.positions[j] - self.positions[i])
+    }
+
+    fn estimate(&self) -> f64 {
+        if self.count >= 5 {
+            self.heights[2]
+        } else {
+            let mut samples = self.heights[..self.count].to_vec();
+            samples.sort_by(|a, b| a.total_cmp(b));
+            exact_quantile(&samples, self.p)
+        }
+    }
+}
+
+/// nearest-rank quantile of sorted samples
+fn exact_quantile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0f64;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.max(1) - 1]
+}
+
+#[derive(Debug)]
+enum Quantiles {
+    Exact(Vec<f64>),
+    Approx(Vec<P2>),
+}
+
+/// Running summary (count/mean/min/max/quantiles) of a metric
+#[derive(Debug)]
+pub struct Summary {
+    name: String,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+    quantiles: Quantiles,
+}
+
+impl Summary {
+    pub fn new(name: &str, approx: bool) -> Self {
+        let quantiles = if approx {
+            Quantiles::Approx(QUANTILES.iter().map(|p| P2::new(*p)).collect())
+        } else {
+            Quantiles::Exact(vec![])
+        };
+        Self {
+            name: name.to_owned(),
+            count: 0,
+            sum: 0f64,
+            min: f64::MAX,
+            max: f64::MIN,
+            quantiles,
+        }
+    }
+
+    /// header line describing how quantiles are computed
+    pub fn header(approx: bool) -> &'static str {
+        if approx {
+            "summary,name,count,mean,min,max,p50,p95 (approximate P² quantiles: bounded memory, typically within a few percent of exact)"
+        } else {
+            "summary,name,count,mean,min,max,p50,p95 (exact quantiles: memory grows with the sample count)"
+        }
+    }
+
+    pub fn add(&mut self, x: f64) {
+        self.count += 1;
+        self.sum += x;
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        match &mut self.quantiles {
+            Quantiles::Exact(samples) => samples.push(x),
+            Quantiles::Approx(estimators) => estimators.iter_mut().for_each(|e| e.add(x)),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// minimum and maximum of the samples, zeros if empty
+    pub fn range(&self) -> (f64, f64) {
+        if self.count == 0 {
+            (0f64, 0f64)
+        } else {
+            (self.min, self.max)
+        }
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0f64
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    pub fn quantiles(&self) -> Vec<f64> {
+        match &self.quantiles {
+            Quantiles::Exact(samples) => {
+                let mut sorted = samples.clone();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                QUANTILES.iter().map(|p| exact_quantile(&sorted, *p)).collect()
+            }
+            Quantiles::Approx(estimators) => estimators.iter().map(|e| e.estimate()).collect(),
+        }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (min, max) = self.range();
+        let quantiles = self.quantiles();
+        write!(
+            f,
+            "summary,{},{},{:.3},{:.3},{:.3},{:.3},{:.3}",
+            self.name,
+            self.count,
+            self.mean(),
+            min,
+            max,
+            quantiles[0],
+            quantiles[1]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// skewed like latencies, the squares of a permutation of 1..=4000
+    fn samples() -> Vec<f64> {
+        (0..4000u64)
+            .map(|i| ((i * 1237 % 4000 + 1).pow(2)) as f64 / 1000f64)
+            .collect()
+    }
+
+    #[test]
+    fn approx_quantiles_near_exact() {
+        let (mut exact, mut approx) = (Summary::new("latency", false), Summary::new("latency", true));
+        samples().into_iter().for_each(|x| {
+            exact.add(x);
+            approx.add(x);
+        });
+        assert_eq!(exact.quantiles(), vec![4000f64, 14440f64]);
+        exact
+            .quantiles()
+            .iter()
+            .zip(approx.quantiles())
+            .for_each(|(e, a)| assert!((a - e).abs() / e < 0.02, "approximate {} against exact {}", a, e));
+        assert_eq!(exact.mean(), approx.mean());
+        assert_eq!(exact.range(), approx.range());
+    }
+
+    #[test]
+    fn approx_quantiles_few_samples() {
+        let mut approx = Summary::new("latency", true);
+        assert_eq!(approx.quantiles(), vec![0f64, 0f64]);
+        [30f64, 10f64, 20f64].iter().for_each(|x| approx.add(*x));
+        assert_eq!(approx.quantiles(), vec![20f64, 30f64]);
+        assert_eq!(approx.range(), (10f64, 30f64));
+    }
+}
@@ -0,0 +1,320 @@
+//! JSON-RPC transport which renames methods for non-standard nodes
+//! and backs off when a node rate limits requests with HTTP 429, with jitter so that
+//! parallel clients do not retry in lockstep
+//!
+//! Remappable methods are the ones `TestClient` calls:
+//! `eth_chainId`, `eth_blockNumber`, `eth_getBlockByNumber`, `eth_getBlockByHash`,
+//! `eth_getTransactionCount`, `eth_getBlockTransactionCountByNumber`, `eth_gasPrice`, `eth_maxPriorityFeePerGas`,
+//! `eth_getCode`, `eth_getTransactionByHash`, `eth_getTransactionReceipt`, `eth_getBlockReceipts`, `eth_accounts`,
+//! `eth_getBalance`, `eth_estimateGas`, `eth_getLogs`, `eth_sendRawTransaction` and `eth_call`.
+
+use futures::future::BoxFuture;
+use jsonrpc_core::{Call, Id, Output, Request, Value};
+use lazy_static::lazy_static;
+use reqwest::{
+    header::{HeaderMap, CONTENT_TYPE, RETRY_AFTER},
+    Client, StatusCode, Url,
+};
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+use web3::{error::TransportError, helpers, BatchTransport, RequestId, Transport};
+
+lazy_static! {
+    static ref RPC_OVERRIDES: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+}
+
+/// idle keep-alive connections kept per node, unbounded like reqwest by default, 0 reconnects for every request
+static HTTP_POOL_SIZE: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// times a rate limited request is retried before its 429 is returned
+const RATE_LIMIT_RETRIES: u32 = 8;
+/// wait before retrying a rate limited request without Retry-After, doubled every retry, then jittered
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_millis(500);
+const RATE_LIMIT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// process-wide `Jitter` of the backoff, full by default
+static BACKOFF_JITTER: AtomicU8 = AtomicU8::new(Jitter::Full as u8);
+
+/// How a backoff is randomized, see https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jitter {
+    /// a random wait up to the backoff
+    Full,
+    /// half of the backoff plus a random wait up to the other half
+    Equal,
+    /// the backoff itself
+    None,
+}
+
+impl std::str::FromStr for Jitter {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "equal" => Ok(Self::Equal),
+            "none" => Ok(Self::None),
+            _ => Err("Invalid jitter: full, equal and none are supported".to_owned()),
+        }
+    }
+}
+
+impl Jitter {
+    /// the wait of a retry whose exponential backoff is `backoff`
+    fn apply(self, backoff: Duration) -> Duration {
+        let random = |up_to: Duration| {
+            let millis = up_to.as_millis() as u64;
+            Duration::from_millis(RandomState::new().build_hasher().finish() % (millis + 1))
+        };
+        match self {
+            Self::Full => random(backoff),
+            Self::Equal => backoff / 2 + random(backoff - backoff / 2),
+            Self::None => backoff,
+        }
+    }
+}
+
+/// set the process-wide jitter of rate limit backoffs
+pub fn set_backoff_jitter(jitter: Jitter) {
+    BACKOFF_JITTER.store(jitter as u8, Ordering::Relaxed);
+}
+
+pub fn backoff_jitter() -> Jitter {
+    match BACKOFF_JITTER.load(Ordering::Relaxed) {
+        j if j == Jitter::Equal as u8 => Jitter::Equal,
+        j if j == Jitter::None as u8 => Jitter::None,
+        _ => Jitter::Full,
+    }
+}
+
+/// 429 responses received and milliseconds spent backing off them, process-wide
+static RATE_LIMITED: AtomicU64 = AtomicU64::new(0);
+static RATE_LIMITED_MS: AtomicU64 = AtomicU64::new(0);
+
+/// transport used to talk to nodes
+pub type NodeTransport = RemapTransport<HttpTransport>;
+
+/// set the process-wide idle connection pool size of http clients created afterwards
+pub fn set_http_pool_size(size: usize) {
+    HTTP_POOL_SIZE.store(size, Ordering::Relaxed);
+}
+
+pub fn http_pool_size() -> usize {
+    HTTP_POOL_SIZE.load(Ordering::Relaxed)
+}
+
+/// 429 responses received so far and the time spent backing off them
+pub fn rate_limited() -> (u64, Duration) {
+    (
+        RATE_LIMITED.load(Ordering::Relaxed),
+        Duration::from_millis(RATE_LIMITED_MS.load(Ordering::Relaxed)),
+    )
+}
+
+/// Logs the rate limiting of the whole process when dropped, keep one alive until exit
+#[derive(Debug, Default)]
+pub struct RateLimitReport;
+
+impl Drop for RateLimitReport {
+    fn drop(&mut self) {
+        let (responses, waited) = rate_limited();
+        if responses > 0 {
+            log::warn!(
+                "{} requests rate limited by nodes (HTTP 429), {:.1}s spent backing off",
+                responses,
+                waited.as_secs_f64()
+            );
+        }
+    }
+}
+
+/// wait asked for by `Retry-After`, either seconds or an http date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => {
+            let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+            let wait = at.timestamp_millis() - chrono::Utc::now().timestamp_millis();
+            Some(Duration::from_millis(wait.max(0) as u64))
+        }
+    }
+}
+
+fn transport_err(e: impl std::fmt::Display) -> web3::Error {
+    web3::Error::Transport(TransportError::Message(e.to_string()))
+}
+
+/// post a json-rpc payload, retrying while the node answers 429
+async fn post(client: Client, url: Url, body: Vec<u8>) -> web3::Result<Vec<u8>> {
+    let jitter = backoff_jitter();
+    let mut backoff = RATE_LIMIT_BACKOFF;
+    let mut retries = 0;
+    loop {
+        let response = client
+            .post(url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.clone())
+            .send()
+            .await
+            .map_err(|e| transport_err(format!("failed to send request: {}", e)))?;
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS && retries < RATE_LIMIT_RETRIES {
+            // the node knows best when it asks for a wait
+            let wait = retry_after(response.headers())
+                .unwrap_or_else(|| jitter.apply(backoff))
+                .min(RATE_LIMIT_MAX_BACKOFF);
+            backoff = (backoff * 2).min(RATE_LIMIT_MAX_BACKOFF);
+            retries += 1;
+            RATE_LIMITED.fetch_add(1, Ordering::Relaxed);
+            RATE_LIMITED_MS.fetch_add(wait.as_millis() as u64, Ordering::Relaxed);
+            log::debug!("{} rate limited the request, retry {} in {:?}", url, retries, wait);
+            tokio::time::sleep(wait).await;
+            continue;
+        }
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| transport_err(format!("failed to read response: {}", e)))?;
+        if !status.is_success() {
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                RATE_LIMITED.fetch_add(1, Ordering::Relaxed);
+            }
+            return Err(web3::Error::Transport(TransportError::Code(status.as_u16())));
+        }
+        return Ok(bytes.to_vec());
+    }
+}
+
+fn invalid_response(e: serde_json::Error, body: &[u8]) -> web3::Error {
+    web3::Error::InvalidResponse(format!("{}: {}", e, String::from_utf8_lossy(body)))
+}
+
+/// HTTP JSON-RPC transport, the same as web3's one but aware of rate limiting
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: Client,
+    url: Url,
+    id: Arc<AtomicUsize>,
+}
+
+impl HttpTransport {
+    pub fn new(client: Client, url: Url) -> Self {
+        Self {
+            client,
+            url,
+            id: Arc::new(AtomicUsize::new(1)),
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    type Out = BoxFuture<'static, web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        let id = self.id.fetch_add(1, Ordering::Relaxed);
+        (id, helpers::build_request(id, method, params))
+    }
+
+    fn send(&self, _id: RequestId, request: Call) -> Self::Out {
+        let (client, url) = (self.client.clone(), self.url.clone());
+        Box::pin(async move {
+            let body = serde_json::to_vec(&Request::Single(request)).map_err(transport_err)?;
+            let response = post(client, url, body).await?;
+            let output: Output = serde_json::from_slice(&response).map_err(|e| invalid_response(e, &response))?;
+            helpers::to_result_from_output(output)
+        })
+    }
+}
+
+impl BatchTransport for HttpTransport {
+    type Batch = BoxFuture<'static, web3::Result<Vec<web3::Result<Value>>>>;
+
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let (ids, calls): (Vec<_>, Vec<_>) = requests.into_iter().unzip();
+        let (client, url) = (self.client.clone(), self.url.clone());
+        Box::pin(async move {
+            let body = serde_json::to_vec(&Request::Batch(calls)).map_err(transport_err)?;
+            let response = post(client, url, body).await?;
+            let outputs: Vec<Output> = serde_json::from_slice(&response).map_err(|e| invalid_response(e, &response))?;
+            // nodes may answer a batch in any order, results follow the requests
+            let mut outputs = outputs
+                .into_iter()
+                .map(|output| (output.id().clone(), output))
+                .collect::<HashMap<_, _>>();
+            Ok(ids
+                .into_iter()
+                .map(|id| match outputs.remove(&Id::Num(id as u64)) {
+                    Some(output) => helpers::to_result_from_output(output),
+                    None => Err(web3::Error::InvalidResponse(format!("no response to request {}", id))),
+                })
+                .collect())
+        })
+    }
+}
+
+/// replace the process-wide method overrides, each one is `name=actual`
+pub fn set_rpc_overrides(overrides: &[String]) -> Result<(), String> {
+    let mut map = HashMap::new();
+    for o in overrides {
+        match o.split_once('=') {
+            Some((name, actual)) if !name.trim().is_empty() && !actual.trim().is_empty() => {
+                map.insert(name.trim().to_owned(), actual.trim().to_owned());
+            }
+            _ => return Err(format!("Invalid rpc override {}, expect name=actual", o)),
+        }
+    }
+    *RPC_OVERRIDES.write().unwrap() = map;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct RemapTransport<T> {
+    inner: T,
+    overrides: Arc<HashMap<String, String>>,
+}
+
+impl<T: Transport> RemapTransport<T> {
+    /// wrap a transport with the current process-wide overrides
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            overrides: Arc::new(RPC_OVERRIDES.read().unwrap().clone()),
+        }
+    }
+}
+
+impl<T: Transport> Transport for RemapTransport<T> {
+    type Out = T::Out;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        let method = self.overrides.get(method).map_or(method, String::as_str);
+        self.inner.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        self.inner.send(id, request)
+    }
+}
+
+impl<T: BatchTransport> BatchTransport for RemapTransport<T> {
+    type Batch = T::Batch;
+
+    /// requests are renamed by `prepare`, nothing is left to do here
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        self.inner.send_batch(requests)
+    }
+}
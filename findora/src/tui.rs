@@ -0,0 +1,376 @@
+//! Live dashboard of a run, drawn in the alternate screen when `--tui` is given
+
+use crate::metrics::{Metrics, Snapshot};
+use crossterm::{
+    cursor::Show,
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use feth::TestClient;
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Paragraph, Row, Sparkline, Table},
+    Frame, Terminal,
+};
+use std::{
+    collections::VecDeque,
+    io::{self, Stdout, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use web3::types::{Address, BlockId};
+
+/// redraw interval
+const TICK: Duration = Duration::from_millis(250);
+/// node polling interval
+const NODE_POLL: Duration = Duration::from_secs(1);
+/// log lines kept for the log panel, also printed when the dashboard exits
+const LOG_LINES: usize = 200;
+/// latency points kept for the sparkline, one per tick
+const SPARK_POINTS: usize = 240;
+
+/// whether the dashboard owns the terminal
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// log target which keeps the lines for the dashboard while it is shown, and writes to stderr otherwise
+#[derive(Debug, Clone, Default)]
+pub struct LogTail(Arc<Mutex<VecDeque<String>>>);
+
+impl Write for LogTail {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !ACTIVE.load(Ordering::SeqCst) {
+            return io::stderr().write(buf);
+        }
+        let mut lines = self.0.lock().unwrap();
+        for line in String::from_utf8_lossy(buf).lines() {
+            if lines.len() == LOG_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_owned());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct NodeHealth {
+    url: String,
+    height: Option<u64>,
+    pending: Option<u64>,
+    rpc: Option<Duration>,
+    chain_tps: Option<f64>,
+}
+
+/// leave the alternate screen and restore the terminal, safe to call more than once
+pub fn restore() {
+    if ACTIVE.swap(false, Ordering::SeqCst) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+    }
+}
+
+/// Handle of the running dashboard, the terminal is restored when it is dropped
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Dashboard {
+    /// take over the terminal and draw `metrics`, if any, and the health of `nodes` until dropped,
+    /// the nodes are polled with the `root` account of the run, if any
+    pub fn start(
+        nodes: Vec<String>,
+        timeouts: (Option<u64>, Option<u64>),
+        root: Option<(secp256k1::SecretKey, Address)>,
+        metrics: Option<Arc<Metrics>>,
+        logs: LogTail,
+    ) -> io::Result<Self> {
+        enable_raw_mode()?;
+        ACTIVE.store(true, Ordering::SeqCst);
+        let terminal = execute!(io::stdout(), EnterAlternateScreen)
+            .and_then(|_| Terminal::new(CrosstermBackend::new(io::stdout())))
+            .map_err(|e| {
+                restore();
+                e
+            })?;
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore();
+            hook(info)
+        }));
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let nodes = nodes
+            .into_iter()
+            .map(|url| poll_node(url, timeouts, root, stop.clone()))
+            .collect::<Vec<_>>();
+        let handle = std::thread::spawn({
+            let stop = stop.clone();
+            move || run(terminal, metrics, nodes, logs, stop)
+        });
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// poll the height, mempool and block TPS of a node in the background
+fn poll_node(
+    url: String,
+    (timeout, node_timeout): (Option<u64>, Option<u64>),
+    root: Option<(secp256k1::SecretKey, Address)>,
+    stop: Arc<AtomicBool>,
+) -> Arc<Mutex<NodeHealth>> {
+    let health = Arc::new(Mutex::new(NodeHealth {
+        url: url.clone(),
+        height: None,
+        pending: None,
+        rpc: None,
+        chain_tps: None,
+    }));
+    let shared = health.clone();
+    std::thread::spawn(move || {
+        let client = TestClient::setup_with_root(Some(url), timeout, node_timeout, root);
+        let mut last: Option<(u64, Instant)> = None;
+        while !stop.load(Ordering::SeqCst) {
+            let begin = Instant::now();
+            let height = client.block_number().map(|h| h.as_u64());
+            let rpc = begin.elapsed();
+            let pending = height.and(client.pending_tx_count()).map(|p| p.low_u64());
+            let chain_tps = match (height, last) {
+                (Some(height), Some((prev, at))) if height > prev => {
+                    let txs = (prev + 1..=height)
+                        .filter_map(|h| client.block_with_tx_hashes_inner(BlockId::Number(h.into()), None, Some(1)))
+                        .map(|b| b.transactions.len())
+                        .sum::<usize>();
+                    Some(txs as f64 / at.elapsed().as_secs_f64())
+                }
+                _ => None,
+            };
+            if let Some(height) = height.filter(|h| last.map_or(true, |(prev, _)| *h > prev)) {
+                last = Some((height, Instant::now()));
+            }
+            {
+                let mut health = shared.lock().unwrap();
+                health.height = height;
+                health.pending = pending;
+                health.rpc = height.map(|_| rpc);
+                if chain_tps.is_some() || height.is_none() {
+                    health.chain_tps = chain_tps;
+                }
+            }
+            while !stop.load(Ordering::SeqCst) && begin.elapsed() < NODE_POLL {
+                std::thread::sleep(TICK);
+            }
+        }
+    });
+    health
+}
+
+/// everything drawn by one frame
+struct View {
+    elapsed: Duration,
+    snapshot: Option<Snapshot>,
+    inflight: u64,
+    tps: f64,
+    latency: VecDeque<u64>,
+    nodes: Vec<NodeHealth>,
+    logs: Vec<String>,
+}
+
+fn run(
+    mut terminal: Terminal<CrosstermBackend<Stdout>>,
+    metrics: Option<Arc<Metrics>>,
+    nodes: Vec<Arc<Mutex<NodeHealth>>>,
+    logs: LogTail,
+    stop: Arc<AtomicBool>,
+) {
+    let start = Instant::now();
+    // (time, sent, latency sum) of the last second, to derive the current TPS and latency
+    let mut window = VecDeque::new();
+    let mut latency = VecDeque::with_capacity(SPARK_POINTS);
+    let mut interrupted = false;
+    while !stop.load(Ordering::SeqCst) {
+        let snapshot = metrics.as_ref().map(|m| m.snapshot());
+        let (inflight, tps) = match (metrics.as_ref(), snapshot.as_ref()) {
+            (Some(metrics), Some(snapshot)) => {
+                let (sent, sum) = (snapshot.sent, metrics.latency_sum());
+                if let Some((_, prev_sent, prev_sum)) = window.back() {
+                    if latency.len() == SPARK_POINTS {
+                        latency.pop_front();
+                    }
+                    latency.push_back((sum - prev_sum).checked_div(sent - prev_sent).unwrap_or(0));
+                }
+                window.push_back((Instant::now(), sent, sum));
+                if window.len() > (Duration::from_secs(1).as_millis() / TICK.as_millis()) as usize + 1 {
+                    window.pop_front();
+                }
+                let (at, first, _) = window.front().unwrap();
+                let elapsed = at.elapsed().as_secs_f64();
+                let tps = if elapsed > 0f64 {
+                    (sent - first) as f64 / elapsed
+                } else {
+                    0f64
+                };
+                (metrics.inflight(), tps)
+            }
+            _ => (0, 0f64),
+        };
+        let view = View {
+            elapsed: start.elapsed(),
+            snapshot,
+            inflight,
+            tps,
+            latency: latency.clone(),
+            nodes: nodes.iter().map(|n| n.lock().unwrap().clone()).collect(),
+            logs: logs.0.lock().unwrap().iter().cloned().collect(),
+        };
+        if terminal.draw(|f| draw(f, &view)).is_err() {
+            break;
+        }
+
+        match event::poll(TICK).and_then(|ready| if ready { event::read().map(Some) } else { Ok(None) }) {
+            Ok(Some(Event::Key(key)))
+                if key.code == KeyCode::Char('q')
+                    || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)) =>
+            {
+                interrupted = true;
+                break;
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+
+    restore();
+    // the log lines shown in the dashboard are gone with the alternate screen
+    let mut stderr = io::stderr();
+    for line in logs.0.lock().unwrap().drain(..) {
+        let _ = writeln!(stderr, "{}", line);
+    }
+    if interrupted {
+        interrupt();
+    }
+}
+
+/// act as if ctrl-c reached the process, raw mode swallows it
+#[cfg(unix)]
+fn interrupt() {
+    let _ = signal_hook::low_level::raise(signal_hook::consts::SIGINT);
+}
+
+#[cfg(not(unix))]
+fn interrupt() {
+    std::process::exit(130);
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, view: &View) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(8),
+                Constraint::Length(view.nodes.len() as u16 + 3),
+                Constraint::Min(3),
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+    let bordered = |title: &'static str| Block::default().title(title).borders(Borders::ALL);
+
+    let header = format!(
+        "elapsed {}s | TPS {:.1} | in flight {} | press q to quit",
+        view.elapsed.as_secs(),
+        view.tps,
+        view.inflight
+    );
+    f.render_widget(Paragraph::new(header).block(bordered("run")), chunks[0]);
+
+    let tallies = match &view.snapshot {
+        Some(s) => format!(
+            "sent {} | succeed {} | reverted {} | failed {} | duplicates {} | error rate {:.3} | p50 {:.0} ms | p95 {:.0} ms",
+            s.sent, s.succeed, s.reverted, s.failed, s.duplicates, s.error_rate, s.latency_p50, s.latency_p95
+        ),
+        None => "no per-transaction metrics for this command".to_owned(),
+    };
+    f.render_widget(Paragraph::new(tallies).block(bordered("transactions")), chunks[1]);
+
+    // only the latest points fit in the panel
+    let width = chunks[2].width.saturating_sub(2) as usize;
+    let latency = view.latency.iter().copied().collect::<Vec<_>>();
+    let latency = &latency[latency.len().saturating_sub(width)..];
+    f.render_widget(
+        Sparkline::default()
+            .block(bordered("latency ms"))
+            .data(latency)
+            .style(Style::default().fg(Color::Yellow)),
+        chunks[2],
+    );
+
+    let or_dash = |v: Option<String>| v.unwrap_or_else(|| "-".to_owned());
+    let rows = view
+        .nodes
+        .iter()
+        .map(|n| {
+            let (status, color) = if n.height.is_some() {
+                ("up", Color::Green)
+            } else {
+                ("down", Color::Red)
+            };
+            Row::new(vec![
+                n.url.clone(),
+                or_dash(n.height.map(|h| h.to_string())),
+                or_dash(n.pending.map(|p| p.to_string())),
+                or_dash(n.rpc.map(|d| d.as_millis().to_string())),
+                or_dash(n.chain_tps.map(|t| format!("{:.1}", t))),
+                status.to_owned(),
+            ])
+            .style(Style::default().fg(color))
+        })
+        .collect::<Vec<_>>();
+    let widths = [
+        Constraint::Percentage(40),
+        Constraint::Percentage(12),
+        Constraint::Percentage(12),
+        Constraint::Percentage(12),
+        Constraint::Percentage(12),
+        Constraint::Percentage(12),
+    ];
+    f.render_widget(
+        Table::new(rows)
+            .header(
+                Row::new(vec!["node", "height", "pending", "rpc ms", "chain TPS", "status"])
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            )
+            .block(bordered("nodes"))
+            .widths(&widths),
+        chunks[3],
+    );
+
+    let height = chunks[4].height.saturating_sub(2) as usize;
+    let logs = view.logs[view.logs.len().saturating_sub(height)..].join("\n");
+    f.render_widget(Paragraph::new(logs).block(bordered("log")), chunks[4]);
+}
@@ -1,7 +1,7 @@
 use sha3::{Digest, Keccak256};
 use std::{path::Path, str::FromStr};
 use url::Url;
-use web3::types::{Address, H256};
+use web3::types::{Address, H256, U256};
 
 pub fn log_cpus() -> u64 {
     num_cpus::get() as u64
@@ -42,14 +42,19 @@ where
     P: AsRef<Path>,
 {
     let sk_str = std::fs::read_to_string(secret).unwrap();
-    let root_sk = secp256k1::SecretKey::from_str(sk_str.trim()).unwrap();
+    parse_keypair(&sk_str).unwrap()
+}
+
+/// parse a hex private key, with or without `0x`, and derive its address
+pub fn parse_keypair(secret: &str) -> Result<(secp256k1::SecretKey, Address), secp256k1::Error> {
+    let root_sk = secp256k1::SecretKey::from_str(secret.trim().trim_start_matches("0x"))?;
     let s = secp256k1::Secp256k1::signing_only();
     let root_pk = secp256k1::PublicKey::from_secret_key(&s, &root_sk);
     let mut res = [0u8; 64];
     res.copy_from_slice(&root_pk.serialize_uncompressed()[1..65]);
     let root_addr = Address::from(H256::from_slice(Keccak256::digest(&res).as_slice()));
 
-    (root_sk, root_addr)
+    Ok((root_sk, root_addr))
 }
 
 pub fn check_parallel_args(max_par: u64) {
@@ -72,3 +77,38 @@ pub fn calc_pool_size(keys: usize, max_par: usize) -> usize {
     }
     max_pool_size
 }
+
+/// insert a comma between every three digits of an integer string
+pub fn group_digits(digits: &str) -> String {
+    let len = digits.len();
+    let mut grouped = String::with_capacity(len + len / 3);
+    for (idx, c) in digits.chars().enumerate() {
+        if idx > 0 && (len - idx) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+/// format wei as eth with a fixed number of decimal places, extra digits are truncated
+pub fn format_eth(wei: U256, decimals: usize, grouped: bool) -> String {
+    let unit = U256::exp10(18);
+    let integer = (wei / unit).to_string();
+    let integer = if grouped { group_digits(&integer) } else { integer };
+    if decimals == 0 {
+        return integer;
+    }
+    let mut fraction = format!("{:0>18}", (wei % unit).to_string());
+    fraction.truncate(decimals);
+    format!("{}.{:0<width$}", integer, fraction, width = decimals)
+}
+
+/// format wei as a plain integer
+pub fn format_wei(wei: U256, grouped: bool) -> String {
+    if grouped {
+        group_digits(&wei.to_string())
+    } else {
+        wei.to_string()
+    }
+}
@@ -0,0 +1,103 @@
+//! Workload files of `--transactions-from-file`, one json transaction per line:
+//!
+//! ```json
+//! {"from_index": 0, "to": "0x...", "value": "1000000", "data": "0xa9059cbb...", "gas": 60000}
+//! ```
+//!
+//! `from_index` is the index of the sending account in the source key file, `value` is in wei,
+//! decimal or `0x` hex, and `value`, `data` and `gas` are optional. Transactions of the same
+//! account are sent in file order, accounts in parallel.
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::{collections::BTreeMap, path::Path};
+use web3::types::{Address, Bytes, U256};
+
+#[derive(Debug, Deserialize)]
+struct Line {
+    from_index: usize,
+    to: Address,
+    #[serde(default)]
+    value: Option<Value>,
+    #[serde(default)]
+    data: Bytes,
+    #[serde(default)]
+    gas: Option<u64>,
+}
+
+/// One transaction of a workload
+#[derive(Debug, Clone)]
+pub struct WorkloadTx {
+    pub to: Address,
+    pub value: U256,
+    pub data: Bytes,
+    /// the run gas limit if none
+    pub gas: Option<u64>,
+}
+
+/// Transactions of a workload grouped by sending account, in file order
+#[derive(Debug, Default)]
+pub struct Workload(pub BTreeMap<usize, Vec<WorkloadTx>>);
+
+impl Workload {
+    /// read a workload file, every account index must be below `accounts`
+    pub fn load(path: &Path, accounts: usize) -> anyhow::Result<Self> {
+        let mut workload = Self::default();
+        for (idx, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid =
+                |why: String| anyhow::anyhow!("invalid transaction at line {} of {:?}: {}", idx + 1, path, why);
+            let tx: Line = serde_json::from_str(line).map_err(|e| invalid(e.to_string()))?;
+            if tx.from_index >= accounts {
+                return Err(invalid(format!(
+                    "account index {} is out of the {} loaded source accounts",
+                    tx.from_index, accounts
+                )));
+            }
+            let value = match tx.value {
+                None => U256::zero(),
+                Some(value) => parse_value(&value).ok_or_else(|| invalid(format!("invalid value {}", value)))?,
+            };
+            workload.0.entry(tx.from_index).or_default().push(WorkloadTx {
+                to: tx.to,
+                value,
+                data: tx.data,
+                gas: tx.gas,
+            });
+        }
+        if workload.is_empty() {
+            anyhow::bail!("no transaction in workload {:?}", path);
+        }
+        log::info!(
+            "{} transactions of {} accounts loaded from {:?}",
+            workload.len(),
+            workload.0.len(),
+            path
+        );
+        Ok(workload)
+    }
+
+    /// number of transactions
+    pub fn len(&self) -> usize {
+        self.0.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.values().all(Vec::is_empty)
+    }
+}
+
+/// wei as a json number, a decimal string or a `0x` hex string
+fn parse_value(value: &Value) -> Option<U256> {
+    match value {
+        Value::Number(n) => n.as_u64().map(U256::from),
+        Value::String(s) => match s.strip_prefix("0x") {
+            Some(hex) => U256::from_str_radix(hex, 16).ok(),
+            None => U256::from_dec_str(s).ok(),
+        },
+        _ => None,
+    }
+}